@@ -1,6 +1,9 @@
 use std::fmt::{Display, Formatter};
 
-use crate::{Constant, Expr, FnDef, Ident, Program, Stmt, Tree};
+use crate::{
+    BinaryOp, Block, BlockItem, Constant, Decl, Expr, Fixity, FnDef, Ident, IncrementOp, Program,
+    Stmt, Tree, UnaryOp,
+};
 
 trait DisplayNode {
     fn fmt_node(&self, indent: usize, tree: &Tree, f: &mut Formatter<'_>);
@@ -12,7 +15,7 @@ trait DisplayNode {
     }
 }
 
-impl<'src> Display for Tree<'src> {
+impl Display for Tree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // find program definition and go from there
         let prog_node = self.program_node();
@@ -47,12 +50,50 @@ impl DisplayNode for FnDef {
         self.writeln(0, ",", f);
 
         self.write(indent, "Body = ", f);
-        tree[self.body].fmt_node(indent + 1, tree, f);
+        tree[self.body].fmt_node(indent, tree, f);
 
         self.writeln(indent, ")", f);
     }
 }
 
+impl DisplayNode for Block {
+    fn fmt_node(&self, indent: usize, tree: &Tree, f: &mut Formatter<'_>) {
+        self.writeln(0, "[", f);
+        for item in &tree[self.items] {
+            item.fmt_node(indent + 1, tree, f);
+        }
+        self.writeln(indent, "]", f);
+    }
+}
+
+impl DisplayNode for BlockItem {
+    fn fmt_node(&self, indent: usize, tree: &Tree, f: &mut Formatter<'_>) {
+        match self {
+            BlockItem::Stmt(stmt) => {
+                self.write(indent, "", f);
+                tree[stmt].fmt_node(indent, tree, f);
+            }
+            BlockItem::Decl(decl) => tree[decl].fmt_node(indent, tree, f),
+        }
+    }
+}
+
+impl DisplayNode for Decl {
+    fn fmt_node(&self, indent: usize, tree: &Tree, f: &mut Formatter<'_>) {
+        self.write(indent, "Declare(", f);
+        tree[self.name].fmt_node(indent + 1, tree, f);
+
+        match self.init {
+            Some(init) => {
+                self.writeln(0, " =", f);
+                tree[init].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            None => self.writeln(0, ")", f),
+        }
+    }
+}
+
 impl DisplayNode for Stmt {
     fn fmt_node(&self, indent: usize, tree: &Tree, f: &mut Formatter<'_>) {
         match self {
@@ -82,6 +123,21 @@ impl DisplayNode for Stmt {
                     }
                 }
             }
+            Stmt::Expression { expr } => {
+                self.writeln(0, "Expression(", f);
+                tree[expr].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Stmt::Compound { block } => {
+                self.write(0, "Block", f);
+                tree[block].fmt_node(indent, tree, f);
+            }
+            Stmt::Null => {
+                self.writeln(0, "Null", f);
+            }
+            Stmt::Error { .. } => {
+                self.writeln(0, "Error", f);
+            }
         }
     }
 }
@@ -100,12 +156,114 @@ impl DisplayNode for Expr {
                 tree[constant].fmt_node(indent + 1, tree, f);
                 self.writeln(0, ")", f);
             }
+            Expr::Var { ident } => {
+                self.write(indent, "Var(", f);
+                tree[ident].fmt_node(indent + 1, tree, f);
+                self.writeln(0, ")", f);
+            }
+            Expr::Assign { dst, src, .. } => {
+                self.writeln(indent, "Assign(", f);
+                tree[dst].fmt_node(indent + 1, tree, f);
+                tree[src].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Expr::CompoundAssign { op, dst, src, .. } => {
+                self.writeln(indent, &format!("CompoundAssign({op},"), f);
+                tree[dst].fmt_node(indent + 1, tree, f);
+                tree[src].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Expr::Conditional {
+                cond,
+                cond_true,
+                cond_false,
+            } => {
+                self.writeln(indent, "Conditional(", f);
+                tree[cond].fmt_node(indent + 1, tree, f);
+                tree[cond_true].fmt_node(indent + 1, tree, f);
+                tree[cond_false].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Expr::Increment {
+                op,
+                fixity,
+                operand,
+                ..
+            } => {
+                self.writeln(indent, &format!("{fixity}{op}("), f);
+                tree[operand].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Expr::Unary { op, operand } => {
+                self.writeln(indent, &format!("Unary({op},"), f);
+                tree[operand].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+            Expr::Binary { op, left, right } => {
+                self.writeln(indent, &format!("Binary({op},"), f);
+                tree[left].fmt_node(indent + 1, tree, f);
+                tree[right].fmt_node(indent + 1, tree, f);
+                self.writeln(indent, ")", f);
+            }
+        }
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOp::Negate => write!(f, "Negate"),
+            UnaryOp::Complement => write!(f, "Complement"),
+            UnaryOp::Not => write!(f, "Not"),
+        }
+    }
+}
+
+impl Display for IncrementOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncrementOp::Increment => write!(f, "Increment"),
+            IncrementOp::Decrement => write!(f, "Decrement"),
+        }
+    }
+}
+
+impl Display for Fixity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fixity::Prefix => write!(f, "Prefix"),
+            Fixity::Postfix => write!(f, "Postfix"),
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "Add"),
+            BinaryOp::Subtract => write!(f, "Subtract"),
+            BinaryOp::Multiply => write!(f, "Multiply"),
+            BinaryOp::Divide => write!(f, "Divide"),
+            BinaryOp::Remainder => write!(f, "Remainder"),
+            BinaryOp::BitAnd => write!(f, "BitAnd"),
+            BinaryOp::BitOr => write!(f, "BitOr"),
+            BinaryOp::BitXor => write!(f, "BitXor"),
+            BinaryOp::ShiftLeft => write!(f, "ShiftLeft"),
+            BinaryOp::ShiftRight => write!(f, "ShiftRight"),
+            BinaryOp::And => write!(f, "And"),
+            BinaryOp::Or => write!(f, "Or"),
+            BinaryOp::Equal => write!(f, "Equal"),
+            BinaryOp::NotEqual => write!(f, "NotEqual"),
+            BinaryOp::LessThan => write!(f, "LessThan"),
+            BinaryOp::LessOrEqual => write!(f, "LessOrEqual"),
+            BinaryOp::GreaterThan => write!(f, "GreaterThan"),
+            BinaryOp::GreaterOrEqual => write!(f, "GreaterOrEqual"),
         }
     }
 }
 
 impl DisplayNode for Ident {
     fn fmt_node(&self, _indent: usize, tree: &Tree, f: &mut Formatter<'_>) {
-        self.write(0, tree.tokens.token_text(self.token.handle), f);
+        self.write(0, &tree.tokens.symbols()[self.name], f);
     }
 }
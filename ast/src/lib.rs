@@ -1,6 +1,6 @@
 use std::ops::Index;
 
-use lex::{Token, TokenizedOutput};
+use lex::{Token, TokenizedOutput, symbol::Symbol, token::Span};
 
 pub mod fmt;
 
@@ -36,7 +36,7 @@ macro_rules! node_type(
             }
         }
 
-        impl<'src> Index<$typeId> for Tree<'src> {
+        impl Index<$typeId> for Tree {
             type Output = $type;
 
             // SAFETY: Since the ast is immutable and we only construct valid `NodeId`s, all `NodeId`s
@@ -46,7 +46,7 @@ macro_rules! node_type(
             }
         }
 
-        impl<'src> Index<&$typeId> for Tree<'src> {
+        impl Index<&$typeId> for Tree {
             type Output = $type;
 
             // SAFETY: Since the ast is immutable and we only construct valid `NodeId`s, all `NodeId`s
@@ -78,6 +78,8 @@ pub struct Node {
 pub enum NodeKind {
     Program(Program),
     FnDef(FnDef),
+    Block(Block),
+    Decl(Decl),
     Stmt(Stmt),
     Expr(Expr),
     Ident(Ident),
@@ -94,7 +96,36 @@ pub struct Program {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct FnDef {
     pub name: IdentId,
-    pub body: StmtId,
+    pub body: BlockId,
+}
+
+/// Statements and declarations between '{' and '}', which get their own scope
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Block {
+    pub items: BlockItems,
+}
+
+/// A variable declaration, like `int a = 1;`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Decl {
+    pub name: IdentId,
+    pub init: Option<ExprId>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlockItem {
+    Stmt(StmtId),
+    Decl(DeclId),
+}
+
+/// The items of a block, in order
+///
+/// Nodes can't own a list, so the items themselves live in [`Tree::block_items`] and this is the
+/// range they take there, see [`Tree::push_block_items`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlockItems {
+    start: usize,
+    len: usize,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -111,15 +142,138 @@ pub enum Stmt {
         // Else
         cond_false: Option<StmtId>,
     },
+    /// An expression evaluated only for its side effects, like `a = 1;`
+    Expression {
+        expr: ExprId,
+    },
+    /// A block used as a statement
+    Compound {
+        block: BlockId,
+    },
+    /// A lone ';'
+    Null,
+    /// A statement which couldn't be parsed, spanning the tokens skipped because of it
+    Error {
+        span: Span,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Expr {
-    Constant { constant: ConstantId },
+    Constant {
+        constant: ConstantId,
+    },
+    Var {
+        ident: IdentId,
+    },
+    /// `dst = src`, which only the semantic analysis checks `dst` can be assigned to
+    Assign {
+        dst: ExprId,
+        src: ExprId,
+        /// The '=' token
+        token: Token,
+    },
+    /// `dst op= src`, like `a += 1`
+    CompoundAssign {
+        op: BinaryOp,
+        dst: ExprId,
+        src: ExprId,
+        /// The 'op=' token
+        token: Token,
+    },
+    /// `cond ? cond_true : cond_false`
+    Conditional {
+        cond: ExprId,
+        cond_true: ExprId,
+        cond_false: ExprId,
+    },
+    /// '++' or '--', either before or after its operand
+    Increment {
+        op: IncrementOp,
+        fixity: Fixity,
+        operand: ExprId,
+        /// The '++' or '--' token
+        token: Token,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: ExprId,
+    },
+    Binary {
+        op: BinaryOp,
+        left: ExprId,
+        right: ExprId,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+    /// '-'
+    Negate,
+    /// '~'
+    Complement,
+    /// '!'
+    Not,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IncrementOp {
+    /// '++'
+    Increment,
+    /// '--'
+    Decrement,
+}
+
+/// Whether an operator comes before its operand, like `++a`, or after it, like `a++`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Fixity {
+    Prefix,
+    Postfix,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    /// '+'
+    Add,
+    /// '-'
+    Subtract,
+    /// '*'
+    Multiply,
+    /// '/'
+    Divide,
+    /// '%'
+    Remainder,
+    /// '&'
+    BitAnd,
+    /// '|'
+    BitOr,
+    /// '^'
+    BitXor,
+    /// '<<'
+    ShiftLeft,
+    /// '>>'
+    ShiftRight,
+    /// '&&'
+    And,
+    /// '||'
+    Or,
+    /// '=='
+    Equal,
+    /// '!='
+    NotEqual,
+    /// '<'
+    LessThan,
+    /// '<='
+    LessOrEqual,
+    /// '>'
+    GreaterThan,
+    /// '>='
+    GreaterOrEqual,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Ident {
+    pub name: Symbol,
     pub token: Token,
 }
 
@@ -136,6 +290,10 @@ pub struct ProgramId(usize);
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct FnDefId(usize);
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlockId(usize);
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DeclId(usize);
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct StmtId(usize);
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ExprId(usize);
@@ -146,6 +304,8 @@ pub struct ConstantId(usize);
 
 node_type!(Program, ProgramId);
 node_type!(FnDef, FnDefId);
+node_type!(Block, BlockId);
+node_type!(Decl, DeclId);
 node_type!(Stmt, StmtId);
 node_type!(Expr, ExprId);
 node_type!(Ident, IdentId);
@@ -168,23 +328,38 @@ node_type!(Constant, ConstantId);
 ///
 /// [type "int", expr "0", return, function "main"]
 #[derive(Debug, Clone)]
-pub struct Tree<'src> {
-    pub tokens: TokenizedOutput<'src>,
+pub struct Tree {
+    pub tokens: TokenizedOutput,
     pub nodes: Vec<Node>,
+    /// Items of every block, each block's items stored contiguously
+    pub block_items: Vec<BlockItem>,
 }
 
-impl<'src> Tree<'src> {
-    pub fn new(tokens: TokenizedOutput<'src>) -> Tree<'src> {
+impl Tree {
+    pub fn new(tokens: TokenizedOutput) -> Tree {
         Tree {
             tokens,
             nodes: Vec::new(),
+            block_items: Vec::new(),
         }
     }
 
-    pub fn with_capacity(tokens: TokenizedOutput<'src>, cap: usize) -> Tree<'src> {
+    pub fn with_capacity(tokens: TokenizedOutput, cap: usize) -> Tree {
         Tree {
             tokens,
             nodes: Vec::with_capacity(cap),
+            block_items: Vec::new(),
+        }
+    }
+
+    /// Stores the items of a block, which can then be accessed by indexing with the returned range
+    pub fn push_block_items(&mut self, items: &[BlockItem]) -> BlockItems {
+        let start = self.block_items.len();
+        self.block_items.extend_from_slice(items);
+
+        BlockItems {
+            start,
+            len: items.len(),
         }
     }
 
@@ -197,6 +372,14 @@ impl<'src> Tree<'src> {
         (self.nodes.len() - 1).into()
     }
 
+    /// Points `ident` to another name, the only change made to nodes after parsing, so that
+    /// semantic analysis can give every variable a unique name
+    pub fn rename(&mut self, ident: IdentId, name: Symbol) {
+        if let NodeKind::Ident(ident) = &mut self.nodes[ident.0].kind {
+            ident.name = name;
+        }
+    }
+
     pub fn program_node(&self) -> &Node {
         let program_index = self
             .nodes
@@ -208,7 +391,15 @@ impl<'src> Tree<'src> {
     }
 }
 
-impl Index<NodeId> for Tree<'_> {
+impl Index<BlockItems> for Tree {
+    type Output = [BlockItem];
+
+    fn index(&self, items: BlockItems) -> &Self::Output {
+        &self.block_items[items.start..items.start + items.len]
+    }
+}
+
+impl Index<NodeId> for Tree {
     type Output = Node;
 
     // SAFETY: Since the ast is immutable and we only construct valid `NodeId`s, all `NodeId`s
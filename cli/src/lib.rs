@@ -1,8 +1,14 @@
 use ast::Tree;
 use codegen::Codegen;
-use lex::{Lexer, TokenizedOutput};
-use parse::Parser;
+use lex::{
+    LexErrors, Lexer, TokenizedOutput,
+    source_map::{FileId, SourceMap},
+};
+use parse::{ParseErrors, Parser};
+use preprocess::{PreprocessError, Preprocessor};
+use sema::SemaErrors;
 use std::{
+    collections::BTreeMap,
     error::Error,
     io::{Write, stderr, stdout},
     path::{Path, PathBuf},
@@ -13,11 +19,25 @@ use x86::{X86, lower};
 
 /// Cli arguments
 ///
-/// rustcc <path> --[lex|parse|codegen]
+/// rustcc <path> --[lex|parse|validate|codegen] [-I dir]... [-D name[=value]]... [-U name]...
 #[derive(Debug, PartialEq)]
 pub struct Args {
     pub file: PathBuf,
     pub mode: CompilationMode,
+    /// Directories searched for included files, in order
+    pub include_paths: Vec<PathBuf>,
+    /// Macros defined or undefined from the command line, in the order they were given
+    pub macros: Vec<MacroFlag>,
+    /// Whether to preprocess with the system's `cc -E` instead of the builtin preprocessor
+    pub external_preprocessor: bool,
+}
+
+/// A `-D` or `-U` flag
+#[derive(Debug, PartialEq)]
+pub enum MacroFlag {
+    /// Either "NAME" or "NAME=value"
+    Define(String),
+    Undefine(String),
 }
 
 impl Default for Args {
@@ -25,6 +45,9 @@ impl Default for Args {
         Args {
             file: PathBuf::default(),
             mode: CompilationMode::Full,
+            include_paths: Vec::new(),
+            macros: Vec::new(),
+            external_preprocessor: false,
         }
     }
 }
@@ -37,6 +60,8 @@ pub enum CompilationMode {
     Lex,
     /// Stop after parsing
     Parse,
+    /// Stop after semantic analysis
+    Validate,
     /// Stop after generating tacky IR
     Tacky,
     /// Stop after codegen, doesn't emit assembly file
@@ -56,6 +81,9 @@ pub enum CompilationMode {
 pub enum CliError {
     NoFileArg,
     NoSuchFile,
+    MissingFlagValue(String),
+    /// The external preprocessor failed, with the errors it printed
+    ExternalPreprocessor(String),
 }
 
 impl std::fmt::Display for CliError {
@@ -63,31 +91,48 @@ impl std::fmt::Display for CliError {
         match self {
             CliError::NoFileArg => write!(f, "No file was provided"),
             CliError::NoSuchFile => write!(f, "File doesn't exist"),
+            CliError::MissingFlagValue(flag) => write!(f, "Missing value after '{flag}'"),
+            CliError::ExternalPreprocessor(errors) => {
+                write!(f, "External preprocessor failed:\n{errors}")
+            }
         }
     }
 }
 
 impl Error for CliError {}
 
-pub fn parse_args(mut args: Vec<String>) -> Result<Args, CliError> {
+pub fn parse_args(args: Vec<String>) -> Result<Args, CliError> {
     let mut constructed_args = Args::default();
 
     // First argument is the executable name
-    args.remove(0);
+    let mut args = args.into_iter().skip(1);
 
-    if args.is_empty() {
-        // TODO No file argument
-        return Err(CliError::NoFileArg);
-    }
-
-    while let Some(arg) = args.pop() {
+    while let Some(arg) = args.next() {
         match arg.as_str() {
+            "-I" => match args.next() {
+                Some(dir) => constructed_args.include_paths.push(dir.into()),
+                None => return Err(CliError::MissingFlagValue(arg)),
+            },
+            dir if dir.starts_with("-I") => constructed_args.include_paths.push(dir[2..].into()),
+            "-D" | "-U" => match args.next() {
+                Some(name) if arg == "-D" => constructed_args.macros.push(MacroFlag::Define(name)),
+                Some(name) => constructed_args.macros.push(MacroFlag::Undefine(name)),
+                None => return Err(CliError::MissingFlagValue(arg)),
+            },
+            name if name.starts_with("-D") => constructed_args
+                .macros
+                .push(MacroFlag::Define(name[2..].to_string())),
+            name if name.starts_with("-U") => constructed_args
+                .macros
+                .push(MacroFlag::Undefine(name[2..].to_string())),
             "--lex" => constructed_args.mode = CompilationMode::Lex,
             "--parse" => constructed_args.mode = CompilationMode::Parse,
+            "--validate" => constructed_args.mode = CompilationMode::Validate,
             "--tacky" => constructed_args.mode = CompilationMode::Tacky,
             "--codegen" => constructed_args.mode = CompilationMode::Codegen,
             "-S" => constructed_args.mode = CompilationMode::NakedAssembly,
             "--full" => constructed_args.mode = CompilationMode::Full,
+            "--external-preprocessor" => constructed_args.external_preprocessor = true,
             file => {
                 let path: PathBuf = file.into();
                 constructed_args.file = path;
@@ -118,48 +163,183 @@ pub fn usage_help() {
     println!("Options:");
     println!("  --lex: Only runs the lexer");
     println!("  --parse: Only runs the parser");
+    println!("  --validate: Runs up to semantic analysis");
     println!("  --tacky: Runs up to tacky lowering");
     println!("  --codegen: Runs up to codegen but doesn't emit any file");
     println!("  -S: Emits naked assembly file");
     println!("  --full: Runs the whole pipeline and outputs final executable");
+    println!("  -I <dir>: Adds a directory to search for included files");
+    println!("  -D <name>[=value]: Defines a macro, with a value of 1 if none is given");
+    println!("  -U <name>: Undefines a macro");
+    println!("  --external-preprocessor: Preprocesses with `cc -E` instead of the builtin one");
+}
+
+/// Formats a compilation error like other compilers do, one `file:line:col: error: message` line
+/// per error so editors can jump to them
+pub fn format_error(sources: &SourceMap, file: FileId, error: &(dyn Error + 'static)) -> String {
+    if let Some(error) = error.downcast_ref::<PreprocessError>() {
+        format!(
+            "{}:{}: error: {error}",
+            sources.path(error.location.file).display(),
+            error.location.position
+        )
+    } else if let Some(LexErrors { errors }) = error.downcast_ref::<LexErrors>() {
+        // Only `TokenizedOutput` knows about linemarkers, which say which file each error is
+        // actually in. Lexing is cheap and this only runs once compilation has already failed, so
+        // we just lex again instead of threading it through
+        let mut lexed = BTreeMap::new();
+
+        errors
+            .iter()
+            .map(|e| {
+                let tokens = lexed
+                    .entry(e.file)
+                    .or_insert_with(|| Lexer::new(sources, e.file).tokenize());
+                let (path, position) = tokens.presumed_position(e.span.start);
+
+                format!("{}:{position}: error: {e}", path.display())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else if let Some(ParseErrors { errors }) = error.downcast_ref::<ParseErrors>() {
+        let errors = errors.iter().map(|e| (e.span.start, e.to_string()));
+        format_errors_in(sources, file, errors)
+    } else if let Some(SemaErrors { errors }) = error.downcast_ref::<SemaErrors>() {
+        let errors = errors.iter().map(|e| (e.span.start, e.to_string()));
+        format_errors_in(sources, file, errors)
+    } else {
+        format!("{}: error: {error}", sources.path(file).display())
+    }
+}
+
+/// Formats errors in a file which was lexed as a whole, each one given by the offset it's at and
+/// its message
+fn format_errors_in(
+    sources: &SourceMap,
+    file: FileId,
+    errors: impl Iterator<Item = (usize, String)>,
+) -> String {
+    // See above for why it's lexed again
+    let tokens = Lexer::new(sources, file).tokenize();
+
+    errors
+        .map(|(offset, message)| {
+            let (path, position) = tokens.presumed_position(offset);
+
+            format!("{}:{position}: error: {message}", path.display())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Runs the preprocessor on `file`, adding the resulting source code to `sources` under the same
+/// path
+pub fn preprocess(
+    sources: &mut SourceMap,
+    file: FileId,
+    args: &Args,
+) -> Result<FileId, Box<dyn Error>> {
+    let mut preprocessor = Preprocessor::new(sources, args.include_paths.clone());
+
+    for flag in &args.macros {
+        match flag {
+            MacroFlag::Define(definition) => preprocessor.define_macro(definition)?,
+            MacroFlag::Undefine(name) => preprocessor.undefine_macro(name)?,
+        }
+    }
+
+    let output = preprocessor.preprocess(file)?.to_string();
+    let path = sources.path(file).to_path_buf();
+
+    Ok(sources.add_file(path, output))
 }
 
-pub fn lex<'src>(src: &'src str) -> Result<TokenizedOutput<'src>, Box<dyn Error>> {
-    Ok(Lexer::lex(src)?)
+/// Runs the system's preprocessor on `args.file`, adding the resulting source code to `sources`
+/// under the same path
+///
+/// Unlike the book's driver we don't pass `-P`, since the linemarkers it removes are what let us
+/// report errors in their original files and lines.
+pub fn external_preprocess(sources: &mut SourceMap, args: &Args) -> Result<FileId, Box<dyn Error>> {
+    let mut preprocessor = Command::new("cc");
+    preprocessor.arg("-E");
+
+    for dir in &args.include_paths {
+        preprocessor.arg("-I").arg(dir);
+    }
+    for flag in &args.macros {
+        match flag {
+            MacroFlag::Define(definition) => preprocessor.arg(format!("-D{definition}")),
+            MacroFlag::Undefine(name) => preprocessor.arg(format!("-U{name}")),
+        };
+    }
+    preprocessor.arg(&args.file);
+
+    info!(
+        "Running command: {:?} {:?}",
+        preprocessor.get_program(),
+        preprocessor.get_args()
+    );
+
+    let output = preprocessor.output()?;
+
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(Box::new(CliError::ExternalPreprocessor(errors)));
+    }
+
+    let source = String::from_utf8(output.stdout)?;
+
+    Ok(sources.add_file(&args.file, source))
+}
+
+pub fn lex(sources: &SourceMap, file: FileId) -> Result<TokenizedOutput, Box<dyn Error>> {
+    Ok(Lexer::lex_file(sources, file)?)
 }
 
-pub fn parse<'src>(src: &'src str) -> Result<Tree<'src>, Box<dyn Error>> {
-    let tokens = lex(src)?;
+pub fn parse(sources: &SourceMap, file: FileId) -> Result<Tree, Box<dyn Error>> {
+    let tokens = lex(sources, file)?;
     let mut parser = Parser::from_tokens(tokens);
-    parser.parse();
-    Ok(parser.nodes.clone())
+    parser.parse()?;
+    Ok(parser.nodes)
+}
+
+pub fn validate(sources: &SourceMap, file: FileId) -> Result<Tree, Box<dyn Error>> {
+    let mut ast = parse(sources, file)?;
+    sema::resolve(&mut ast)?;
+    Ok(ast)
 }
 
-pub fn tacky<'src>(src: &'src str) -> Result<X86, Box<dyn Error>> {
-    let ast = parse(src)?;
+pub fn tacky(sources: &SourceMap, file: FileId) -> Result<X86, Box<dyn Error>> {
+    let ast = validate(sources, file)?;
     Ok(lower(&ast))
 }
 
-pub fn codegen(src: &str) -> Result<X86, Box<dyn Error>> {
-    tacky(src)
+pub fn codegen(sources: &SourceMap, file: FileId) -> Result<X86, Box<dyn Error>> {
+    tacky(sources, file)
 }
 
-pub fn assembly_string(src: &str) -> Result<String, Box<dyn Error>> {
-    let x86 = codegen(src)?;
+pub fn assembly_string(sources: &SourceMap, file: FileId) -> Result<String, Box<dyn Error>> {
+    let x86 = codegen(sources, file)?;
     Ok(Codegen::emit_from_input(&x86)?)
 }
 
-pub fn naked_assembly(src: &str, output_file: &Path) -> Result<(), Box<dyn Error>> {
-    let assembly = assembly_string(src)?;
+pub fn naked_assembly(
+    sources: &SourceMap,
+    file: FileId,
+    output_file: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let assembly = assembly_string(sources, file)?;
     std::fs::write(output_file, &assembly)?;
     Ok(())
 }
 
-pub fn full(src: &str, input_file: &Path) -> Result<PathBuf, Box<dyn Error>> {
+/// Compiles `file` into an executable next to it
+pub fn full(sources: &SourceMap, file: FileId) -> Result<PathBuf, Box<dyn Error>> {
+    let input_file = sources.path(file);
     let mut assembly_file = PathBuf::from(input_file);
     assembly_file.set_extension("s");
 
-    naked_assembly(src, &assembly_file)?;
+    naked_assembly(sources, file, &assembly_file)?;
     let mut executable_file = PathBuf::from(input_file);
     executable_file.set_extension("");
 
@@ -181,8 +361,8 @@ pub fn full(src: &str, input_file: &Path) -> Result<PathBuf, Box<dyn Error>> {
     match linker_output {
         Ok(ok) => {
             info!("Executable generated");
-            stdout().write_all(&ok.stdout);
-            stderr().write_all(&ok.stderr);
+            let _ = stdout().write_all(&ok.stdout);
+            let _ = stderr().write_all(&ok.stderr);
         }
         Err(e) => {
             error!("Got linker error: {e:?}");
@@ -1,4 +1,5 @@
-use std::{fs::OpenOptions, path::PathBuf};
+use lex::source_map::{FileId, SourceMap};
+use std::{error::Error, fs::OpenOptions, path::PathBuf, process::exit};
 use tracing::{error, info};
 use tracing_subscriber::{
     fmt::{self, format::FmtSpan},
@@ -7,6 +8,14 @@ use tracing_subscriber::{
 };
 
 use cli::*;
+
+/// Reports a compilation error and exits with a non-zero status
+fn fail(sources: &SourceMap, file: FileId, error: Box<dyn Error>) -> ! {
+    error!("Compilation failed: {error}");
+    eprintln!("{}", format_error(sources, file, error.as_ref()));
+    exit(1)
+}
+
 fn main() {
     // Create a log file, overriding any pre-existing ones
     let log_file = OpenOptions::new()
@@ -38,36 +47,57 @@ fn main() {
             .collect(),
     ) {
         Ok(args) => {
-            let input = std::fs::read_to_string(&args.file).unwrap();
+            let mut sources = SourceMap::new();
+            let main = match sources.load(&args.file) {
+                Ok(main) => main,
+                Err(e) => {
+                    error!("Couldn't read input file: {e}");
+                    eprintln!("{}: error: {e}", args.file.display());
+                    exit(1)
+                }
+            };
+            let preprocessed = if args.external_preprocessor {
+                external_preprocess(&mut sources, &args)
+            } else {
+                preprocess(&mut sources, main, &args)
+            };
+            let input = match preprocessed {
+                Ok(input) => input,
+                Err(e) => fail(&sources, main, e),
+            };
 
             match args.mode {
-                CompilationMode::Lex => match lex(&input) {
+                CompilationMode::Lex => match lex(&sources, input) {
                     Ok(tokens) => println!("{}", tokens),
-                    Err(e) => panic!("{}", e),
+                    Err(e) => fail(&sources, input, e),
+                },
+                CompilationMode::Parse => match parse(&sources, input) {
+                    Ok(ast) => println!("{}", ast),
+                    Err(e) => fail(&sources, input, e),
                 },
-                CompilationMode::Parse => match parse(&input) {
+                CompilationMode::Validate => match validate(&sources, input) {
                     Ok(ast) => println!("{}", ast),
-                    Err(e) => panic!("{}", e),
+                    Err(e) => fail(&sources, input, e),
                 },
-                CompilationMode::Tacky => match tacky(&input) {
+                CompilationMode::Tacky => match tacky(&sources, input) {
                     Ok(tacky) => println!("{}", tacky),
-                    Err(e) => panic!("{}", e),
+                    Err(e) => fail(&sources, input, e),
                 },
-                CompilationMode::Codegen => match codegen(&input) {
+                CompilationMode::Codegen => match codegen(&sources, input) {
                     Ok(assembly) => println!("{}", assembly),
-                    Err(e) => panic!("{}", e),
+                    Err(e) => fail(&sources, input, e),
                 },
                 CompilationMode::NakedAssembly => {
                     let mut assembly_file = PathBuf::from(&args.file);
                     assembly_file.set_extension("s");
-                    match naked_assembly(&input, &assembly_file) {
+                    match naked_assembly(&sources, input, &assembly_file) {
                         Ok(()) => println!("Generated {assembly_file:?}"),
-                        Err(e) => panic!("{}", e),
+                        Err(e) => fail(&sources, input, e),
                     }
                 }
-                CompilationMode::Full => match full(&input, &args.file) {
+                CompilationMode::Full => match full(&sources, input) {
                     Ok(executable) => println!("Generated {executable:?}"),
-                    Err(e) => panic!("{}", e),
+                    Err(e) => fail(&sources, input, e),
                 },
             }
         }
@@ -83,7 +113,12 @@ fn main() {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::{Args, CliError, CompilationMode, parse_args};
+    use lex::source_map::{FileId, SourceMap};
+
+    use crate::{
+        Args, CliError, CompilationMode, MacroFlag, external_preprocess, format_error, lex, parse,
+        parse_args, preprocess, validate,
+    };
 
     macro_rules! args [
         ($x:expr) => (
@@ -91,6 +126,14 @@ mod tests {
         );
     ];
 
+    /// Source map with `src` as its only file, read from `path`
+    fn source_file(path: &str, src: &str) -> (SourceMap, FileId) {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(path, src);
+
+        (sources, file)
+    }
+
     #[test]
     fn no_args() {
         let args = args![vec![""]];
@@ -110,7 +153,8 @@ mod tests {
             args,
             Ok(Args {
                 file: PathBuf::from(file),
-                mode: CompilationMode::Full
+                mode: CompilationMode::Full,
+                ..Default::default()
             })
         );
     }
@@ -133,7 +177,8 @@ mod tests {
             args,
             Ok(Args {
                 file: PathBuf::from(file),
-                mode: CompilationMode::Lex
+                mode: CompilationMode::Lex,
+                ..Default::default()
             })
         );
     }
@@ -147,4 +192,168 @@ mod tests {
             parse_args(args![vec!["", "--lex", file]])
         );
     }
+
+    #[test]
+    fn lex_errors_have_positions() {
+        let (sources, file) = source_file("file.c", "int main(void) {\n    return 0@1 `;\n}");
+        let error = lex(&sources, file).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "file.c:2:13: error: Invalid char '@'\nfile.c:2:16: error: Invalid char '`'"
+        );
+    }
+
+    #[test]
+    fn parse_errors_have_positions() {
+        let (sources, file) = source_file("file.c", "int main(void) {\n    return 0\n}\nx");
+        let error = parse(&sources, file).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "file.c:3:1: error: Expected [Semicolon], got [CloseBrace]\n\
+             file.c:4:1: error: Expected end of input, got [Ident]"
+        );
+    }
+
+    #[test]
+    fn sema_errors_have_positions() {
+        let (sources, file) = source_file("file.c", "int main(void) {\n    return a;\n}");
+        let error = validate(&sources, file).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "file.c:2:12: error: Use of undeclared variable 'a'"
+        );
+    }
+
+    #[test]
+    fn include_paths() {
+        let file = "Cargo.toml";
+
+        assert_eq!(
+            parse_args(args![vec!["", "-I", "include", file, "-Ilib"]]),
+            Ok(Args {
+                file: PathBuf::from(file),
+                include_paths: vec![PathBuf::from("include"), PathBuf::from("lib")],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_args(args![vec!["", file, "-I"]]),
+            Err(CliError::MissingFlagValue("-I".to_string()))
+        );
+    }
+
+    #[test]
+    fn preprocess_errors_have_positions() {
+        let args = Args {
+            file: PathBuf::from("file.c"),
+            ..Default::default()
+        };
+        let (mut sources, file) = source_file("file.c", "int main(void) {\n    #define\n}");
+        let error = preprocess(&mut sources, file, &args).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "file.c:2:6: error: Macro names must be identifiers"
+        );
+
+        let (mut sources, file) = source_file("file.c", "#ifdef X\nint x;\n");
+        let error = preprocess(&mut sources, file, &args).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "file.c:1:1: error: Unterminated '#ifdef'"
+        );
+    }
+
+    #[test]
+    fn macro_flags() {
+        let file = "Cargo.toml";
+        let args = parse_args(args![vec!["", "-DA", file, "-D", "B=2", "-UA", "-U", "C"]]);
+
+        assert_eq!(
+            args,
+            Ok(Args {
+                file: PathBuf::from(file),
+                macros: vec![
+                    MacroFlag::Define("A".to_string()),
+                    MacroFlag::Define("B=2".to_string()),
+                    MacroFlag::Undefine("A".to_string()),
+                    MacroFlag::Undefine("C".to_string()),
+                ],
+                ..Default::default()
+            })
+        );
+
+        let (mut sources, file) = source_file("main.c", "#ifndef A\nint x = B;\n#endif");
+        let input = preprocess(&mut sources, file, &args.unwrap()).unwrap();
+        assert_eq!(sources.source(input), "\nint x = 2;");
+    }
+
+    #[test]
+    fn lex_errors_follow_line_markers() {
+        let src = "# 1 \"main.c\"\nint x;\n# 12 \"header.h\" 1\nint y = @;\n# 3 \"main.c\" 2\n`";
+        let (sources, file) = source_file("out.i", src);
+        let error = lex(&sources, file).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, file, error.as_ref()),
+            "header.h:12:9: error: Invalid char '@'\nmain.c:3:1: error: Invalid char '`'"
+        );
+    }
+
+    #[test]
+    fn builtin_preprocessor_line_markers() {
+        let args = Args {
+            file: PathBuf::from("file.c"),
+            ..Default::default()
+        };
+        let (mut sources, file) = source_file("file.c", "int x;\n#line 20 \"other.c\"\nint y = @;");
+        let input = preprocess(&mut sources, file, &args).unwrap();
+        let error = lex(&sources, input).unwrap_err();
+
+        assert_eq!(
+            format_error(&sources, input, error.as_ref()),
+            "other.c:20:9: error: Invalid char '@'"
+        );
+    }
+
+    #[test]
+    fn external_preprocessor() {
+        let file = std::env::temp_dir().join("cli_external_preprocessor.c");
+        std::fs::write(
+            &file,
+            "#define RET(x) return x\nint main(void) {\n    RET(VALUE);\n}",
+        )
+        .unwrap();
+
+        let args = parse_args(args![vec![
+            "",
+            file.to_str().unwrap(),
+            "--external-preprocessor",
+            "-DVALUE=3"
+        ]])
+        .unwrap();
+        assert!(args.external_preprocessor);
+
+        let mut sources = SourceMap::new();
+        let input = external_preprocess(&mut sources, &args).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        let tokens = lex(&sources, input).unwrap();
+        let texts: Vec<_> = (0..tokens.len()).map(|i| tokens.token_text(i)).collect();
+        assert_eq!(
+            texts,
+            [
+                "int", "main", "(", "void", ")", "{", "return", "3", ";", "}"
+            ]
+        );
+
+        // Linemarkers point back to the original file
+        let (marker_file, position) = tokens.presumed_position(tokens.token_source(6).span.start);
+        assert_eq!(marker_file, file);
+        assert_eq!(position.line, 2);
+    }
 }
@@ -4,18 +4,23 @@ mod valid {
     use crate::assert_x86;
     use cli::assembly_string;
     use cli::codegen;
+    use lex::source_map::SourceMap;
 
     /// Since all these tests are essentially the same program, they just return 0, we can validate
     /// that they end up being represented exactly the same as x86 ast.
     #[test]
     fn same_representation() {
         let mut programs = Vec::new();
+        let codegen = |src| {
+            let (sources, file) = SourceMap::anonymous(src);
+            codegen(&sources, file).unwrap()
+        };
 
-        let return_0 = codegen(RETURN_0).unwrap();
-        programs.push(codegen(NO_NEWLINES).unwrap());
-        programs.push(codegen(NEWLINES).unwrap());
-        programs.push(codegen(SPACES).unwrap());
-        programs.push(codegen(TABS).unwrap());
+        let return_0 = codegen(RETURN_0);
+        programs.push(codegen(NO_NEWLINES));
+        programs.push(codegen(NEWLINES));
+        programs.push(codegen(SPACES));
+        programs.push(codegen(TABS));
 
         for p in programs {
             assert_eq!(return_0, p);
@@ -128,6 +133,96 @@ mod invalid_lex {
 }
 
 mod invalid_parse {
-    use crate::assert_x86;
-    use cli::codegen;
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn end_before_expr() {
+        let src = "int main(void) {
+            return";
+
+        parse_err!(src, "Expected expression, got end of input");
+    }
+
+    #[test]
+    fn extra_junk() {
+        let src = "int main(void)
+        {
+            return 2;
+        }
+        foo";
+
+        parse_err!(src, "Expected end of input, got [Ident]");
+    }
+
+    #[test]
+    fn invalid_function_name() {
+        let src = "int 3 (void) {
+            return 0;
+        }";
+
+        parse_err!(src, "Expected [Ident], got [IntConstant]");
+    }
+
+    #[test]
+    fn keyword_wrong_case() {
+        let src = "int main(void) {
+            RETURN 0;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [IntConstant]");
+    }
+
+    #[test]
+    fn missing_type() {
+        let src = "main(void) {
+            return 0;
+        }";
+
+        parse_err!(src, "Expected [Keyword(int)], got [Ident]");
+    }
+
+    #[test]
+    fn no_semicolon() {
+        let src = "int main (void) {
+            return 0
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+
+    #[test]
+    fn not_expression() {
+        let src = "int main(void) {
+            return int;
+        }";
+
+        parse_err!(src, "Expected expression, got [Keyword(int)]");
+    }
+
+    #[test]
+    fn switched_parens() {
+        let src = "int main )( {
+            return 0;
+        }";
+
+        parse_err!(src, "Expected [OpenParen], got [CloseParen]");
+    }
+
+    #[test]
+    fn unclosed_brace() {
+        let src = "int main(void) {
+            return 0;";
+
+        parse_err!(src, "Expected [CloseBrace], got end of input");
+    }
+
+    #[test]
+    fn unclosed_paren() {
+        let src = "int main( {
+            return 0;
+        }";
+
+        parse_err!(src, "Expected [Keyword(void)], got [OpenBrace]");
+    }
 }
@@ -0,0 +1,122 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn bitwise() {
+        assert_x86!("int main(void) { return ~12; }");
+    }
+
+    #[test]
+    fn bitwise_int_min() {
+        assert_x86!("int main(void) { return ~-2147483647; }");
+    }
+
+    #[test]
+    fn neg() {
+        assert_x86!("int main(void) { return -5; }");
+    }
+
+    #[test]
+    fn nested_ops() {
+        assert_x86!("int main(void) { return ~-3; }");
+    }
+
+    #[test]
+    fn nested_ops_2() {
+        assert_x86!("int main(void) { return -~0; }");
+    }
+
+    #[test]
+    fn not() {
+        assert_x86!("int main(void) { return !5; }");
+    }
+
+    #[test]
+    fn not_not() {
+        assert_x86!("int main(void) { return !!-3; }");
+    }
+
+    #[test]
+    fn parens() {
+        assert_x86!("int main(void) { return ~(-2); }");
+    }
+
+    #[test]
+    fn redundant_parens() {
+        assert_x86!("int main(void) { return -((((10)))); }");
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn extra_paren() {
+        let src = "int main(void)
+        {
+            return (3));
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseParen]");
+    }
+
+    #[test]
+    fn missing_const() {
+        let src = "int main(void) {
+            return ~;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let src = "int main(void) {
+            return -5
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+
+    #[test]
+    fn nested_missing_const() {
+        let src = "int main(void)
+        {
+            return -~;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn parenthesize_operand() {
+        let src = "int main(void) {
+            return (-)3;
+        }";
+
+        parse_err!(src, "Expected expression, got [CloseParen]");
+    }
+
+    #[test]
+    fn unclosed_paren() {
+        let src = "int main(void)
+        {
+            return (1;
+        }";
+
+        parse_err!(src, "Expected [CloseParen], got [Semicolon]");
+    }
+
+    #[test]
+    fn wrong_order() {
+        let src = "int main(void) {
+            return 4-;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+}
@@ -0,0 +1,160 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn add() {
+        assert_x86!("int main(void) { return 1 + 2; }");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_x86!("int main(void) { return 1 - 2 - 3; }");
+    }
+
+    #[test]
+    fn associativity_and_precedence() {
+        assert_x86!("int main(void) { return 5 * 4 / 2 - 3 % (2 + 1); }");
+    }
+
+    #[test]
+    fn div() {
+        assert_x86!("int main(void) { return 4 / 2; }");
+    }
+
+    #[test]
+    fn div_neg() {
+        assert_x86!("int main(void) { return (-12) / 5; }");
+    }
+
+    #[test]
+    fn rem() {
+        assert_x86!("int main(void) { return 4 % 3; }");
+    }
+
+    #[test]
+    fn mult() {
+        assert_x86!("int main(void) { return 2 * 3; }");
+    }
+
+    #[test]
+    fn precedence() {
+        assert_x86!("int main(void) { return 2 + 3 * 4; }");
+    }
+
+    #[test]
+    fn unop_add() {
+        assert_x86!("int main(void) { return ~2 + 3; }");
+    }
+
+    #[test]
+    fn unop_parens() {
+        assert_x86!("int main(void) { return ~(1 + 1); }");
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        assert_x86!("int main(void) { return 80 >> 2 | 1 ^ 5 & 7 << 1; }");
+    }
+
+    #[test]
+    fn bitwise_shiftr_negative() {
+        assert_x86!("int main(void) { return -5 >> 30; }");
+    }
+
+    #[test]
+    fn bitwise_variable_shift_count() {
+        assert_x86!("int main(void) { return (4 << (2 * 2)) + (100 >> (1 + 2)); }");
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn bitwise_double_operator() {
+        let src = "int main(void) {
+            return 1 | | 2;
+        }";
+
+        parse_err!(src, "Expected expression, got [Pipe]");
+    }
+
+    #[test]
+    fn double_operation() {
+        let src = "int main(void) {
+            return 1 * / 2;
+        }";
+
+        parse_err!(src, "Expected expression, got [FrontSlash]");
+    }
+
+    #[test]
+    fn imbalanced_paren() {
+        let src = "int main(void) {
+            return 1 + (2;
+        }";
+
+        parse_err!(src, "Expected [CloseParen], got [Semicolon]");
+    }
+
+    #[test]
+    fn malformed_paren() {
+        let src = "int main(void) {
+            return 2 (- 3);
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [OpenParen]");
+    }
+
+    #[test]
+    fn misplaced_semicolon() {
+        let src = "int main(void) {
+            return 1 + (2;)
+        }";
+
+        parse_err!(
+            src,
+            "Expected [CloseParen], got [Semicolon]\nExpected expression, got [CloseParen]"
+        );
+    }
+
+    #[test]
+    fn missing_first_op() {
+        let src = "int main(void) {
+            return /3;
+        }";
+
+        parse_err!(src, "Expected expression, got [FrontSlash]");
+    }
+
+    #[test]
+    fn missing_open_paren() {
+        let src = "int main(void) {
+            return 1 + 2);
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseParen]");
+    }
+
+    #[test]
+    fn missing_second_op() {
+        let src = "int main(void) {
+            return 1 + ;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn no_semicolon() {
+        let src = "int main(void) {
+            return 2*2
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn and_short_circuit() {
+        assert_x86!("int main(void) { return 0 && (1 / 0); }");
+    }
+
+    #[test]
+    fn or_short_circuit() {
+        assert_x86!("int main(void) { return 1 || (1 / 0); }");
+    }
+
+    #[test]
+    fn multi_short_circuit() {
+        assert_x86!("int main(void) { return 0 || 0 && (1 / 0); }");
+    }
+
+    #[test]
+    fn compare_arithmetic_results() {
+        assert_x86!("int main(void) { return ~2 * -2 == 1 + 5; }");
+    }
+
+    #[test]
+    fn relational() {
+        assert_x86!("int main(void) { return 1 < 2 != 3 >= 4; }");
+    }
+
+    #[test]
+    fn le_gt() {
+        assert_x86!("int main(void) { return (0 <= 2) + (2 > 1); }");
+    }
+
+    #[test]
+    fn not_sum() {
+        assert_x86!("int main(void) { return !(4 - 4); }");
+    }
+
+    #[test]
+    fn precedence() {
+        assert_x86!("int main(void) { return 1 || 0 && 2; }");
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn missing_const() {
+        let src = "int main(void)
+        {
+            10 <= !;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn missing_first_op() {
+        let src = "int main(void) {
+            return <= 2;
+        }";
+
+        parse_err!(src, "Expected expression, got [LessEqual]");
+    }
+
+    #[test]
+    fn missing_operand() {
+        let src = "int main(void) {
+            return 1 < > 3;
+        }";
+
+        parse_err!(src, "Expected expression, got [Greater]");
+    }
+
+    #[test]
+    fn missing_second_op() {
+        let src = "int main(void) {
+            return 2 && ~;
+        }";
+
+        parse_err!(src, "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let src = "int main(void) {
+            return 1 || 2
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+
+    #[test]
+    fn unary_missing_semicolon() {
+        let src = "int main(void)
+        {
+            return !10
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+}
@@ -0,0 +1,330 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn add_variables() {
+        assert_x86!(
+            "int main(void) {
+                int first_variable = 1;
+                int second_variable = 2;
+                return first_variable + second_variable;
+            }"
+        );
+    }
+
+    #[test]
+    fn assign() {
+        assert_x86!(
+            "int main(void) {
+                int var0;
+                var0 = 2;
+                return var0;
+            }"
+        );
+    }
+
+    #[test]
+    fn assignment_in_initializer() {
+        assert_x86!(
+            "int main(void) {
+                int a;
+                int b = a = 0;
+                return b;
+            }"
+        );
+    }
+
+    #[test]
+    fn empty_function_body() {
+        assert_x86!("int main(void) { }");
+    }
+
+    #[test]
+    fn null_then_return() {
+        assert_x86!("int main(void) { ; return 1; }");
+    }
+
+    #[test]
+    fn unused_exp() {
+        assert_x86!("int main(void) { 2 + 2; return 0; }");
+    }
+
+    #[test]
+    fn use_val_in_own_initializer() {
+        assert_x86!("int main(void) { int a = 0 & a; return a; }");
+    }
+
+    #[test]
+    fn compound_assignment_chained() {
+        assert_x86!(
+            "int main(void) {
+                int a = 250;
+                int b = 200;
+                int c = 100;
+                a -= b *= c /= 2 + 1;
+                return a;
+            }"
+        );
+    }
+
+    #[test]
+    fn compound_bitwise_shiftr() {
+        assert_x86!(
+            "int main(void) {
+                int x = -2147483647;
+                int y = 5;
+                x >>= y %= 3;
+                return x;
+            }"
+        );
+    }
+
+    #[test]
+    fn postfix_incr_and_decr() {
+        assert_x86!(
+            "int main(void) {
+                int a = 1;
+                int b = 2;
+                int c = a++;
+                int d = b--;
+                return a + b + c + d;
+            }"
+        );
+    }
+
+    #[test]
+    fn prefix_incr_and_decr() {
+        assert_x86!(
+            "int main(void) {
+                int a = 1;
+                int b = 2;
+                int c = ++a;
+                int d = --b;
+                return a + b + c + d;
+            }"
+        );
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn declare_keyword_as_var() {
+        let src = "int main(void) {
+            int return = 4;
+            return return + 1;
+        }";
+
+        parse_err!(
+            src,
+            "Expected [Ident], got [Keyword(return)]\nExpected expression, got [Keyword(return)]"
+        );
+    }
+
+    #[test]
+    fn invalid_specifier() {
+        let src = "int main(void) {
+            int foo bar = 3;
+            return bar;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [Ident]");
+    }
+
+    #[test]
+    fn invalid_type() {
+        let src = "int main(void) {
+            ints a = 1;
+            return a;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [Ident]");
+    }
+
+    #[test]
+    fn invalid_variable_name() {
+        let src = "int main(void)
+        {
+            int 10 = 0;
+            return 10;
+        }";
+
+        parse_err!(src, "Expected [Ident], got [IntConstant]");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let src = "int main(void) {
+            int a = 2
+            a = a + 4;
+            return a;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [Ident]");
+    }
+
+    #[test]
+    fn binary_increment() {
+        let src = "int main(void) {
+            int a = 0;
+            return a ++ 1;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [IntConstant]");
+    }
+
+    #[test]
+    fn compound_initializer() {
+        let src = "int main(void) {
+            int a += 0;
+            return a;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [PlusEqual]");
+    }
+
+    #[test]
+    fn increment_declaration() {
+        let src = "int main(void) {
+            int a++;
+            return 0;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [Increment]");
+    }
+
+    #[test]
+    fn return_in_assignment() {
+        let src = "int main(void)
+        {
+            int 10 = return 0;
+        }";
+
+        parse_err!(src, "Expected [Ident], got [IntConstant]");
+    }
+}
+
+mod invalid_semantics {
+    use crate::sema_err;
+    use cli::validate;
+
+    #[test]
+    fn declared_after_use() {
+        let src = "int main(void) {
+            a = 1 + 2;
+            int a;
+            return a;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'a'");
+    }
+
+    #[test]
+    fn invalid_lvalue() {
+        let src = "int main(void) {
+            int a = 2;
+            a + 3 = 4;
+            return a;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+
+    #[test]
+    fn mixed_precedence_assignment() {
+        let src = "int main(void) {
+            int a = 1;
+            int b = 2;
+            a = 3 * b = a;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+
+    #[test]
+    fn compound_invalid_lvalue() {
+        let src = "int main(void) {
+            int a = 10;
+            (a += 1) -= 2;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+
+    #[test]
+    fn postfix_decr_non_lvalue() {
+        let src = "int main(void) {
+            int a = 10;
+            return a++--;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+
+    #[test]
+    fn prefix_decr_non_lvalue() {
+        let src = "int main(void) {
+            return --3;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+
+    #[test]
+    fn undeclared_compound_assignment_use() {
+        let src = "int main(void) {
+            int b = 10;
+            b *= a;
+            return 0;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'a'");
+    }
+
+    #[test]
+    fn undeclared_prefix_incr() {
+        let src = "int main(void) {
+            ++a;
+            return 0;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'a'");
+    }
+
+    #[test]
+    fn redefine() {
+        let src = "int main(void) {
+            int a = 1;
+            int a = 2;
+            return a;
+        }";
+
+        sema_err!(src, "Redeclaration of variable 'a'");
+    }
+
+    #[test]
+    fn undeclared_var_and() {
+        let src = "int main(void) {
+            return 0 && a;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'a'");
+    }
+
+    #[test]
+    fn use_then_redefine() {
+        let src = "int main(void) {
+            int a = 0;
+            return a;
+            int a = 1;
+            return a;
+        }";
+
+        sema_err!(src, "Redeclaration of variable 'a'");
+    }
+}
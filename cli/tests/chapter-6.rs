@@ -0,0 +1,178 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn else_branch() {
+        assert_x86!(
+            "int main(void) {
+                int a = 0;
+                if (a)
+                    return 1;
+                else
+                    return 2;
+            }"
+        );
+    }
+
+    #[test]
+    fn if_nested() {
+        assert_x86!(
+            "int main(void) {
+                int a = 1;
+                int b = 0;
+                if (a)
+                    b = 1;
+                else if (b)
+                    b = 2;
+                return b;
+            }"
+        );
+    }
+
+    #[test]
+    fn if_null_body() {
+        assert_x86!(
+            "int main(void) {
+                int x = 0;
+                if (0)
+                    ;
+                else
+                    x = 1;
+                return x;
+            }"
+        );
+    }
+
+    #[test]
+    fn if_not_taken() {
+        assert_x86!(
+            "int main(void) {
+                int a = 0;
+                int b = 0;
+                if (a)
+                    b = 1;
+                return b;
+            }"
+        );
+    }
+
+    #[test]
+    fn nested_ternary() {
+        assert_x86!(
+            "int main(void) {
+                int a = 1;
+                int b = 2;
+                int flag = 0;
+                return a > b ? 5 : flag ? 6 : 7;
+            }"
+        );
+    }
+
+    #[test]
+    fn ternary_middle_assignment() {
+        assert_x86!(
+            "int main(void) {
+                int a = 1;
+                a != 2 ? a = 2 : 0;
+                return a;
+            }"
+        );
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn declaration_as_statement() {
+        let src = "int main(void) {
+            if (5)
+                int i = 0;
+        }";
+
+        parse_err!(src, "Expected expression, got [Keyword(int)]");
+    }
+
+    #[test]
+    fn empty_if_body() {
+        let src = "int main(void) {
+            if (0) else return 0;
+        }";
+
+        parse_err!(src, "Expected expression, got [Keyword(else)]");
+    }
+
+    #[test]
+    fn if_no_parens() {
+        let src = "int main(void) {
+            if 0 return 1;
+        }";
+
+        parse_err!(src, "Expected [OpenParen], got [IntConstant]");
+    }
+
+    #[test]
+    fn incomplete_ternary() {
+        let src = "int main(void) {
+            return 1 ? 2;
+        }";
+
+        parse_err!(src, "Expected [Colon], got [Semicolon]");
+    }
+
+    #[test]
+    fn malformed_ternary() {
+        let src = "int main(void) {
+            return 1 ? 2 : 3 : 4;
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [Colon]");
+    }
+
+    #[test]
+    fn mismatched_nesting() {
+        let src = "int main(void) {
+            int a = 0;
+            if (1)
+                return 1;
+            else
+                return 2;
+            else
+                return 3;
+        }";
+
+        parse_err!(src, "Expected expression, got [Keyword(else)]");
+    }
+}
+
+mod invalid_semantics {
+    use crate::sema_err;
+    use cli::validate;
+
+    #[test]
+    fn invalid_var_in_if() {
+        let src = "int main(void) {
+            if (1)
+                return c;
+            int c = 0;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'c'");
+    }
+
+    #[test]
+    fn ternary_assign() {
+        let src = "int main(void) {
+            int a = 2;
+            int b = 1;
+            a > b ? a = 1 : a = 0;
+            return a;
+        }";
+
+        sema_err!(src, "Invalid lvalue, only variables can be assigned to");
+    }
+}
@@ -0,0 +1,232 @@
+mod common;
+
+mod valid {
+    use crate::assert_x86;
+    use cli::assembly_string;
+
+    #[test]
+    fn assign_to_self() {
+        assert_x86!(
+            "int main(void) {
+                int a = 3;
+                {
+                    int a = a = 4;
+                }
+                return a;
+            }"
+        );
+    }
+
+    #[test]
+    fn empty_blocks() {
+        assert_x86!(
+            "int main(void) {
+                int ten = 10;
+                {}
+                int twenty = 10 * 2;
+                {{}}
+                return ten + twenty;
+            }"
+        );
+    }
+
+    #[test]
+    fn hidden_then_visible() {
+        assert_x86!(
+            "int main(void) {
+                int a = 2;
+                int b;
+                {
+                    a = -4;
+                    int a = 7;
+                    b = a + 1;
+                }
+                return b == 8 && a == -4;
+            }"
+        );
+    }
+
+    #[test]
+    fn multiple_vars_same_name() {
+        assert_x86!(
+            "int main(void) {
+                int a = 0;
+                {
+                    int b = 4;
+                    a = b;
+                }
+                {
+                    int b = 2;
+                    a = a - b;
+                }
+                return a;
+            }"
+        );
+    }
+
+    #[test]
+    fn nested_if() {
+        assert_x86!(
+            "int main(void) {
+                int a = 0;
+                if (a) {
+                    int b = 2;
+                    return b;
+                } else {
+                    int c = 3;
+                    if (a < c) {
+                        return !a;
+                    } else {
+                        return 5;
+                    }
+                }
+                return a;
+            }"
+        );
+    }
+
+    #[test]
+    fn similar_var_names() {
+        assert_x86!(
+            "int main(void) {
+                int a;
+                int result;
+                int a1 = 1;
+                {
+                    int a = 2;
+                    int a1 = 2;
+                    {
+                        int a = 20;
+                        result = a;
+                    }
+                    result = result + a1;
+                }
+                return result + a1;
+            }"
+        );
+    }
+
+    #[test]
+    fn temporary_names() {
+        assert_x86!(
+            "int main(void) {
+                int tmp = 1;
+                {
+                    int tmp = 2;
+                    return -tmp + tmp++;
+                }
+            }"
+        );
+    }
+}
+
+mod invalid_parse {
+    use crate::parse_err;
+    use cli::parse;
+
+    #[test]
+    fn extra_brace() {
+        let src = "int main(void) {
+            if(0){
+                return 1;
+            }}
+            return 2;
+        }";
+
+        parse_err!(src, "Expected end of input, got [Keyword(return)]");
+    }
+
+    #[test]
+    fn missing_brace() {
+        let src = "int main(void) {
+            if(0){
+                return 1;
+            return 2;
+        }";
+
+        parse_err!(src, "Expected [CloseBrace], got end of input");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let src = "int main(void) {
+            int a = 4;
+            {
+                a = 5;
+                return a
+            }
+        }";
+
+        parse_err!(src, "Expected [Semicolon], got [CloseBrace]");
+    }
+
+    #[test]
+    fn ternary_blocks() {
+        let src = "int main(void) {
+            int a;
+            return 1 ? { a = 2 } : a = 4;
+        }";
+
+        parse_err!(
+            src,
+            "Expected expression, got [OpenBrace]\nExpected end of input, got [Colon]"
+        );
+    }
+}
+
+mod invalid_semantics {
+    use crate::sema_err;
+    use cli::validate;
+
+    #[test]
+    fn double_define() {
+        let src = "int main(void) {
+            {
+                int a;
+                int a;
+            }
+        }";
+
+        sema_err!(src, "Redeclaration of variable 'a'");
+    }
+
+    #[test]
+    fn double_define_after_scope() {
+        let src = "int main(void) {
+            int a = 3;
+            {
+                a = 5;
+            }
+            int a = 2;
+            return a;
+        }";
+
+        sema_err!(src, "Redeclaration of variable 'a'");
+    }
+
+    #[test]
+    fn out_of_scope() {
+        let src = "int main(void) {
+            {
+                int a = 2;
+            }
+            return a;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'a'");
+    }
+
+    #[test]
+    fn use_before_declare() {
+        let src = "int main(void) {
+            int a;
+            {
+                b = 10;
+            }
+            int b;
+            return b;
+        }";
+
+        sema_err!(src, "Use of undeclared variable 'b'");
+    }
+}
@@ -1,16 +1,46 @@
-use insta;
-
 #[macro_export]
 macro_rules! assert_x86 {
     ($input:expr) => {
-        insta::assert_debug_snapshot!(assembly_string($input).unwrap());
+        let (sources, file) = lex::source_map::SourceMap::anonymous($input);
+        insta::assert_debug_snapshot!(assembly_string(&sources, file).unwrap());
     };
 }
 
 #[macro_export]
 macro_rules! lex_err {
     ($src:expr, $expected:expr) => {
-        if let Err(actual) = lex($src) {
+        let (sources, file) = lex::source_map::SourceMap::anonymous($src);
+        if let Err(actual) = lex(&sources, file) {
+            assert_eq!($expected, actual.to_string());
+        } else {
+            panic!(
+                "Expected input '{}' to fail with error '{}'",
+                $src, $expected
+            );
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! parse_err {
+    ($src:expr, $expected:expr) => {
+        let (sources, file) = lex::source_map::SourceMap::anonymous($src);
+        if let Err(actual) = parse(&sources, file) {
+            assert_eq!($expected, actual.to_string());
+        } else {
+            panic!(
+                "Expected input '{}' to fail with error '{}'",
+                $src, $expected
+            );
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! sema_err {
+    ($src:expr, $expected:expr) => {
+        let (sources, file) = lex::source_map::SourceMap::anonymous($src);
+        if let Err(actual) = validate(&sources, file) {
             assert_eq!($expected, actual.to_string());
         } else {
             panic!(
@@ -4,7 +4,8 @@ use tracing::info;
 pub mod error;
 pub use error::*;
 
-use x86::{FnDef, Instruction, Operand, Program, X86};
+use lex::symbol::SymbolTable;
+use x86::{BinaryOp, CondCode, FnDef, Instruction, Operand, Program, Register, UnaryOp, X86};
 
 pub struct Codegen<'input> {
     input: &'input X86,
@@ -20,14 +21,14 @@ impl<'input> Codegen<'input> {
     }
 
     pub fn emit(&'input mut self) -> &'input str {
-        self.input.nodes.fmt(&mut self.output);
+        self.input.nodes.fmt(&self.input.symbols, &mut self.output);
         self.emit_footer();
         &self.output
     }
 
     pub fn emit_from_input(input: &'input X86) -> CodegenResult<String> {
         let mut codegen = Codegen::new(input);
-        codegen.input.nodes.fmt(&mut codegen.output);
+        codegen.input.nodes.fmt(&input.symbols, &mut codegen.output);
         codegen.emit_footer();
         Ok(codegen.output.clone())
     }
@@ -47,40 +48,115 @@ impl<'input> Codegen<'input> {
 /// Very similar to the `Display` trait, but we can't impl it for `X86` since both are defined in
 /// other crates.
 trait Format {
-    fn fmt(&self, string: &mut String);
+    fn fmt(&self, symbols: &SymbolTable, string: &mut String);
 }
 
 impl Format for Program {
-    fn fmt(&self, string: &mut String) {
-        self.main.fmt(string);
+    fn fmt(&self, symbols: &SymbolTable, string: &mut String) {
+        self.main.fmt(symbols, string);
     }
 }
 
 impl Format for FnDef {
-    fn fmt(&self, string: &mut String) {
+    fn fmt(&self, symbols: &SymbolTable, string: &mut String) {
         info!("Formatting function");
+        let name = &symbols[self.name];
         let name = if cfg!(target_os = "macos") {
-            &("_".to_string() + &self.name)
+            &("_".to_string() + name)
         } else {
-            &self.name
+            name
         };
         let _ = writeln!(string, ".globl {}", name);
         let _ = writeln!(string, "{}:", name);
-        self.body.iter().for_each(|instr| instr.fmt(string));
+        let _ = writeln!(string, "    pushq %rbp");
+        let _ = writeln!(string, "    movq %rsp, %rbp");
+        self.body
+            .iter()
+            .for_each(|instr| instr.fmt(symbols, string));
     }
 }
 
 impl Format for Instruction {
-    fn fmt(&self, string: &mut String) {
+    fn fmt(&self, symbols: &SymbolTable, string: &mut String) {
         match self {
             Instruction::Mov { src, dst } => {
                 let _ = write!(string, "    movl ");
-                src.fmt(string);
+                src.fmt(symbols, string);
                 let _ = write!(string, ", ");
-                dst.fmt(string);
+                dst.fmt(symbols, string);
                 let _ = writeln!(string);
             }
+            Instruction::Unary { op, operand } => {
+                let op = match op {
+                    UnaryOp::Neg => "negl",
+                    UnaryOp::Not => "notl",
+                };
+                let _ = write!(string, "    {op} ");
+                operand.fmt(symbols, string);
+                let _ = writeln!(string);
+            }
+            Instruction::Binary { op, src, dst } => {
+                let name = match op {
+                    BinaryOp::Add => "addl",
+                    BinaryOp::Sub => "subl",
+                    BinaryOp::Mult => "imull",
+                    BinaryOp::And => "andl",
+                    BinaryOp::Or => "orl",
+                    BinaryOp::Xor => "xorl",
+                    BinaryOp::Sal => "sall",
+                    BinaryOp::Sar => "sarl",
+                    BinaryOp::Shr => "shrl",
+                };
+                let _ = write!(string, "    {name} ");
+                match op {
+                    // The shift count is a single byte
+                    BinaryOp::Sal | BinaryOp::Sar | BinaryOp::Shr => fmt_byte(src, symbols, string),
+                    _ => src.fmt(symbols, string),
+                }
+                let _ = write!(string, ", ");
+                dst.fmt(symbols, string);
+                let _ = writeln!(string);
+            }
+            Instruction::Cdq => {
+                let _ = writeln!(string, "    cdq");
+            }
+            Instruction::Idiv(operand) => {
+                let _ = write!(string, "    idivl ");
+                operand.fmt(symbols, string);
+                let _ = writeln!(string);
+            }
+            Instruction::Cmp { src, dst } => {
+                let _ = write!(string, "    cmpl ");
+                src.fmt(symbols, string);
+                let _ = write!(string, ", ");
+                dst.fmt(symbols, string);
+                let _ = writeln!(string);
+            }
+            Instruction::SetCC { cond, dst } => {
+                let _ = write!(string, "    set{} ", cond_suffix(*cond));
+                fmt_byte(dst, symbols, string);
+                let _ = writeln!(string);
+            }
+            Instruction::Jmp(target) => {
+                let _ = writeln!(string, "    jmp {}", label(&symbols[*target]));
+            }
+            Instruction::JmpCC { cond, target } => {
+                let _ = writeln!(
+                    string,
+                    "    j{} {}",
+                    cond_suffix(*cond),
+                    label(&symbols[*target])
+                );
+            }
+            Instruction::Label(name) => {
+                let _ = writeln!(string, "{}:", label(&symbols[*name]));
+            }
+            Instruction::AllocateStack(size) => {
+                let _ = writeln!(string, "    subq ${size}, %rsp");
+            }
             Instruction::Ret => {
+                let _ = writeln!(string, "    movq %rbp, %rsp");
+                let _ = writeln!(string, "    popq %rbp");
                 let _ = writeln!(string, "    ret");
             }
         }
@@ -88,14 +164,63 @@ impl Format for Instruction {
 }
 
 impl Format for Operand {
-    fn fmt(&self, string: &mut String) {
+    fn fmt(&self, _symbols: &SymbolTable, string: &mut String) {
         match self {
             Operand::Immediate(value) => {
                 let _ = write!(string, "${}", value);
             }
-            Operand::Register => {
-                let _ = write!(string, "%eax");
+            Operand::Register(reg) => {
+                let name = match reg {
+                    Register::Ax => "%eax",
+                    Register::Cx => "%ecx",
+                    Register::Dx => "%edx",
+                    Register::R10 => "%r10d",
+                    Register::R11 => "%r11d",
+                };
+                let _ = write!(string, "{name}");
+            }
+            Operand::Stack(offset) => {
+                let _ = write!(string, "{offset}(%rbp)");
             }
+            Operand::Pseudo(_) => unreachable!("Pseudos are replaced before emitting code"),
+        }
+    }
+}
+
+/// Suffix of the `jcc` and `setcc` instructions for `cond`
+fn cond_suffix(cond: CondCode) -> &'static str {
+    match cond {
+        CondCode::E => "e",
+        CondCode::NE => "ne",
+        CondCode::L => "l",
+        CondCode::LE => "le",
+        CondCode::G => "g",
+        CondCode::GE => "ge",
+    }
+}
+
+/// Labels get a prefix which keeps them local to the file, and out of the symbol table
+fn label(name: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("L{name}")
+    } else {
+        format!(".L{name}")
+    }
+}
+
+/// Writes `operand` as a single byte, like `setcc` writes to or a shift count
+fn fmt_byte(operand: &Operand, symbols: &SymbolTable, string: &mut String) {
+    match operand {
+        Operand::Register(reg) => {
+            let name = match reg {
+                Register::Ax => "%al",
+                Register::Cx => "%cl",
+                Register::Dx => "%dl",
+                Register::R10 => "%r10b",
+                Register::R11 => "%r11b",
+            };
+            let _ = write!(string, "{name}");
         }
+        operand => operand.fmt(symbols, string),
     }
 }
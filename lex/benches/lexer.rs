@@ -0,0 +1,35 @@
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use lex::Lexer;
+
+/// A few lines of the kind of code found in generated C files, with a bit of everything the lexer
+/// handles
+const CHUNK: &str = r#"/* Generated lookup table
+ * do not edit by hand */
+static const unsigned long table_0x1f[] = { 0x1fUL, 42, 017, 3.14159e+0f, .5 }; // values
+int lookup_entry(int index, const char *name) {
+    // Bounds are checked by the caller
+    if (index >= 0 && index < (int)(sizeof(table_0x1f) / sizeof(table_0x1f[0]))) {
+        return name[index] == '\n' ? table_0x1f[index] << 2 : -1;
+    }
+    return printf("missing entry \"%s\"\t%d\n", name, index);
+}
+
+"#;
+
+fn generated_file(size: usize) -> String {
+    CHUNK.repeat(size / CHUNK.len() + 1)
+}
+
+/// Throughput of lexing a 4 MiB generated file
+fn lex(c: &mut Criterion) {
+    let source = generated_file(4 * 1024 * 1024);
+
+    let mut group = c.benchmark_group("lex");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.sample_size(20);
+    group.bench_function("generated_4mb", |b| b.iter(|| Lexer::lex_all(&source)));
+    group.finish();
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
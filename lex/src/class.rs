@@ -0,0 +1,67 @@
+//! Lookup tables classifying bytes, which is how the lexer decides what to do with each one
+//! without decoding chars
+//!
+//! Only ASCII is classified, bytes of multi-byte chars are never in any class.
+
+const WHITESPACE: u8 = 1 << 0;
+const IDENT_START: u8 = 1 << 1;
+const IDENT: u8 = 1 << 2;
+
+static CLASSES: [u8; 256] = classes();
+
+const fn classes() -> [u8; 256] {
+    let mut classes = [0; 256];
+    let mut byte = 0;
+
+    while byte < 128 {
+        let c = byte as u8;
+
+        // '\n' isn't included since every newline has to be recorded
+        if matches!(c, b' ' | b'\t' | b'\r' | b'\x0b' | b'\x0c') {
+            classes[byte] |= WHITESPACE;
+        }
+        if c.is_ascii_alphabetic() || c == b'_' {
+            classes[byte] |= IDENT_START | IDENT;
+        }
+        if c.is_ascii_digit() {
+            classes[byte] |= IDENT;
+        }
+
+        byte += 1;
+    }
+
+    classes
+}
+
+/// ASCII whitespace other than '\n'
+pub(crate) fn is_whitespace(byte: u8) -> bool {
+    CLASSES[byte as usize] & WHITESPACE != 0
+}
+
+pub(crate) fn is_ident_start(byte: u8) -> bool {
+    CLASSES[byte as usize] & IDENT_START != 0
+}
+
+pub(crate) fn is_ident(byte: u8) -> bool {
+    CLASSES[byte as usize] & IDENT != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::class::{is_ident, is_ident_start, is_whitespace};
+
+    #[test]
+    fn same_as_chars() {
+        for byte in 0..=255u8 {
+            let c = byte as char;
+
+            assert_eq!(
+                is_whitespace(byte),
+                byte.is_ascii() && c.is_whitespace() && c != '\n',
+                "{byte:#x}"
+            );
+            assert_eq!(is_ident_start(byte), c.is_ascii_alphabetic() || c == '_');
+            assert_eq!(is_ident(byte), c.is_ascii_alphanumeric() || c == '_');
+        }
+    }
+}
@@ -1,22 +1,104 @@
 use std::{error::Error, fmt::Display};
 
-#[derive(Debug)]
-pub enum LexError {
+use crate::{source_map::FileId, token::Span};
+
+/// A lexical error and the source text it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub file: FileId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrorKind {
     InvalidChar { c: char },
     InvalidNumericConstant { c: char },
+    InvalidOctalDigit { c: char },
+    InvalidSuffix { suffix: String },
+    MissingHexDigits,
+    MissingExponentDigits,
+    MissingHexFloatExponent,
+    MultipleDecimalPoints,
+    IntegerTooLarge,
+    UnterminatedCharLiteral,
+    UnterminatedStringLiteral,
+    EmptyCharLiteral,
+    InvalidEscape { c: char },
+    MissingEscapeDigits { escape: char },
+    EscapeOutOfRange,
+    InvalidUniversalChar { value: u32 },
 }
 
-impl Display for LexError {
+impl Display for LexErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LexError::InvalidChar { c } => write!(f, "Invalid char '{c}'"),
-            LexError::InvalidNumericConstant { c } => {
+            LexErrorKind::InvalidChar { c } => write!(f, "Invalid char '{c}'"),
+            LexErrorKind::InvalidNumericConstant { c } => {
                 write!(f, "Invalid char in numeric constant '{c}'")
             }
+            LexErrorKind::InvalidOctalDigit { c } => {
+                write!(f, "Invalid digit '{c}' in octal constant")
+            }
+            LexErrorKind::InvalidSuffix { suffix } => {
+                write!(f, "Invalid suffix '{suffix}' on numeric constant")
+            }
+            LexErrorKind::MissingHexDigits => {
+                write!(f, "Expected hexadecimal digits after '0x' prefix")
+            }
+            LexErrorKind::MissingExponentDigits => write!(f, "Exponent has no digits"),
+            LexErrorKind::MissingHexFloatExponent => {
+                write!(f, "Hexadecimal floating constant requires an exponent")
+            }
+            LexErrorKind::MultipleDecimalPoints => {
+                write!(f, "Too many decimal points in numeric constant")
+            }
+            LexErrorKind::IntegerTooLarge => write!(f, "Integer constant is too large"),
+            LexErrorKind::UnterminatedCharLiteral => {
+                write!(f, "Missing terminating ' character")
+            }
+            LexErrorKind::UnterminatedStringLiteral => {
+                write!(f, "Missing terminating \" character")
+            }
+            LexErrorKind::EmptyCharLiteral => write!(f, "Empty char literal"),
+            LexErrorKind::InvalidEscape { c } => write!(f, "Invalid escape sequence '\\{c}'"),
+            LexErrorKind::MissingEscapeDigits { escape } => {
+                write!(f, "Escape sequence '\\{escape}' is missing hex digits")
+            }
+            LexErrorKind::EscapeOutOfRange => write!(f, "Escape sequence out of range"),
+            LexErrorKind::InvalidUniversalChar { value } => {
+                write!(f, "Invalid universal character '\\U{value:08X}'")
+            }
         }
     }
 }
 
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
 impl Error for LexError {}
 
-pub type LexResult<T> = Result<T, LexError>;
+/// Every error found while lexing an input, in the order they appear in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexErrors {
+    pub errors: Vec<LexError>,
+}
+
+impl Display for LexErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LexErrors {}
+
+pub type LexResult<T> = Result<T, LexErrors>;
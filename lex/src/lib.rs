@@ -1,261 +1,642 @@
+mod class;
 pub mod error;
 pub mod line;
+mod literal;
+pub mod source_map;
+pub mod symbol;
 pub mod token;
+mod translation;
+pub mod trivia;
 
 pub use error::*;
+use memchr::{memchr, memchr2};
 pub use token::{Token, TokenType};
 use tracing::{Level, error, span};
 
-use crate::{line::Line, token::TokenSource};
+use crate::{
+    line::{Line, LineMarker},
+    source_map::{FileId, SourceMap},
+    token::{Keyword, Span, TokenSource},
+    translation::{Edit, Translation},
+    trivia::{Trivia, TriviaKind},
+};
 
 pub use output::TokenizedOutput;
 
+/// Lexes the text of a file after translation phases 1 and 2, see [`SourceMap::spelling`]
+///
+/// Offsets into that text are only used while lexing, everything recorded in the output refers to
+/// the original source instead.
 pub struct Lexer<'src> {
-    source: &'src str,
+    file: FileId,
     rest: &'src str,
     offset: usize,
-    output: TokenizedOutput<'src>,
+    translation: Option<&'src Translation>,
+    /// Trigraphs and line splices the cursor hasn't gone past yet
+    edits: &'src [Edit],
+    /// Whether linemarkers and `#line` directives are interpreted instead of lexed as tokens
+    line_markers: bool,
+    /// Whether the end of the input was reached
+    finished: bool,
+    output: TokenizedOutput,
 }
 
-impl Lexer<'_> {
-    pub fn new(source: &str) -> Lexer<'_> {
-        Lexer {
-            source,
-            rest: source,
+impl<'src> Lexer<'src> {
+    /// Creates a lexer for `file`, which must have been added to `sources`
+    pub fn new(sources: &'src SourceMap, file: FileId) -> Lexer<'src> {
+        let source_file = sources.file(file);
+        let translation = source_file.translation();
+
+        let mut lexer = Lexer {
+            file,
+            rest: source_file.translated_source(),
             offset: 0,
-            output: TokenizedOutput::new(source),
-        }
+            translation,
+            edits: translation.map_or(&[], |translation| &translation.edits),
+            line_markers: true,
+            finished: false,
+            output: TokenizedOutput::new(sources.clone(), file),
+        };
+
+        // The file may start with a line splice
+        lexer.bump(0);
+        lexer
     }
 
-    /// Skips whitespace and comments, updating line info on every newline encountered
-    fn skip_whitespace(&mut self) -> Option<char> {
-        let mut chars = self.rest.chars();
-        let mut c;
+    /// Keeps linemarkers and `#line` directives as tokens instead of interpreting them. This is
+    /// what preprocessors need, since they handle those directives themselves.
+    pub fn keep_line_markers(mut self) -> Self {
+        self.line_markers = false;
+        self
+    }
 
-        loop {
-            c = self.skip_comments()?;
+    /// Keeps whitespace and comments as trivia attached to the tokens around them, so the output
+    /// can reproduce the source exactly. See [`TokenizedOutput::leading_trivia`].
+    pub fn keep_trivia(mut self) -> Self {
+        self.output.keep_trivia();
+        self
+    }
 
-            if !c.is_whitespace() {
-                break Some(c);
-            };
+    /// Starts lexing at the beginning of `line` instead of the start of the file, reusing the lines
+    /// and linemarkers `previous` recorded before it
+    ///
+    /// `previous` must come from lexing a text which is the same as this file's up to that line,
+    /// like an older version of a file being edited. If `line` starts inside a block comment,
+    /// lexing starts at the line the comment starts in instead, which [`Lexer::line`] tells.
+    pub fn restart_at(mut self, previous: &TokenizedOutput, line: usize) -> Self {
+        let lines = previous.lines();
+        let mut line = line.min(lines.len().saturating_sub(1));
+
+        while line > 0 && (lines[line - 1].ends_in_comment || lines[line - 1].ends_in_splice) {
+            line -= 1;
+        }
 
-            self.offset += c.len_utf8();
+        self.output.restart_at(previous, line);
 
-            if c == '\n' {
-                let line = if let Some(last_line) = self.output.last_line() {
-                    Line {
-                        start: last_line.end,
-                        end: self.offset,
-                    }
-                } else {
-                    Line {
-                        start: 0,
-                        end: self.offset,
-                    }
-                };
+        // Splices before the line were already recorded as line ends
+        let offset = self.translated(self.output.current_line_offset());
+        self.edits = &self.edits[self.edits.partition_point(|edit| edit.translated < offset)..];
+        self.bump(offset);
+        self
+    }
+
+    /// Line the lexer is at
+    pub fn line(&self) -> usize {
+        self.output.current_line()
+    }
+
+    /// Everything lexed so far
+    pub fn output(&self) -> &TokenizedOutput {
+        &self.output
+    }
 
-                self.output.push_line(line);
+    /// Advances the cursor by `len` bytes, recording the end of every line joined by a line splice
+    /// it goes past
+    fn bump(&mut self, len: usize) {
+        self.offset += len;
+        self.rest = &self.rest[len..];
+
+        while let Some((edit, edits)) = self.edits.split_first()
+            && edit.translated <= self.offset
+        {
+            if edit.splice {
+                self.output.push_line(Line {
+                    start: self.output.current_line_offset(),
+                    end: edit.original,
+                    ends_in_comment: false,
+                    ends_in_splice: true,
+                });
             }
 
-            self.rest = &self.rest[c.len_utf8()..];
+            self.edits = edits;
         }
     }
 
-    /// Skips comments
-    fn skip_comments(&mut self) -> Option<char> {
-        let mut chars = self.rest.chars();
-        let mut c = chars.next()?;
-        let mut in_comment = false;
-        let mut in_multiline_comment = false;
+    /// Offset into the original source of an offset into the lexed text
+    fn original(&self, offset: usize) -> usize {
+        match self.translation {
+            Some(translation) => translation.original_offset(offset),
+            None => offset,
+        }
+    }
 
-        if c == '/' {
-            match chars.next() {
-                Some('/') => in_comment = true,
-                Some('*') => in_multiline_comment = true,
-                Some(_) | None => (),
+    /// Offset into the lexed text of an offset into the original source
+    fn translated(&self, offset: usize) -> usize {
+        match self.translation {
+            Some(translation) => translation.translated_offset(offset),
+            None => offset,
+        }
+    }
+
+    /// Span of the original source lexed as [start, end)
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span {
+            start: self.original(start),
+            end: self.original(end),
+        }
+    }
+
+    /// Source of a token from `start` up to the cursor, which started in `line`
+    fn token_source(&self, start: usize, line: usize) -> TokenSource {
+        TokenSource {
+            file: self.file,
+            span: self.span(start, self.offset),
+            line,
+        }
+    }
+
+    /// Records the line which ends at the current offset, should be called right after consuming
+    /// a '\n'
+    fn end_line(&mut self, in_comment: bool) {
+        let line = Line {
+            start: self.output.current_line_offset(),
+            end: self.original(self.offset),
+            ends_in_comment: in_comment,
+            ends_in_splice: false,
+        };
+
+        self.output.push_line(line);
+    }
+
+    /// Records the source text from `start` up to the cursor as trivia, if it's being kept
+    fn push_trivia(&mut self, kind: TriviaKind, start: usize) {
+        if self.output.keeps_trivia() {
+            let span = self.span(start, self.offset);
+
+            self.output.push_trivia(Trivia { kind, span });
+        }
+    }
+
+    /// Skips whitespace and comments, updating line info on every newline encountered. Returns
+    /// the first byte of what comes after them.
+    fn skip_whitespace(&mut self) -> Option<u8> {
+        loop {
+            let start = self.offset;
+            let byte = *self.rest.as_bytes().first()?;
+
+            if byte == b'\n' {
+                self.bump(1);
+                self.end_line(false);
+                self.push_trivia(TriviaKind::Newline, start);
+            } else if let len @ 1.. = self.whitespace_len() {
+                self.bump(len);
+                self.push_trivia(TriviaKind::Whitespace, start);
+            } else if byte != b'/' || !self.skip_comment() {
+                break Some(byte);
             }
-        } else {
-            return Some(c);
         }
+    }
 
-        self.offset += c.len_utf8() * 2;
-        self.rest = &self.rest[c.len_utf8() * 2..];
+    /// Length of the whitespace at the cursor, not counting newlines
+    fn whitespace_len(&self) -> usize {
+        let bytes = self.rest.as_bytes();
+        let mut len = 0;
 
-        if in_comment {
-            while let Some(c) = chars.next() {
-                self.offset += c.len_utf8();
-                self.rest = &self.rest[c.len_utf8()..];
-                if c == '\n' {
-                    break;
+        while let Some(&byte) = bytes.get(len) {
+            if class::is_whitespace(byte) {
+                len += 1;
+            } else if byte.is_ascii() {
+                break;
+            } else {
+                // Unicode whitespace is rare enough that decoding it is fine
+                match self.rest[len..].chars().next() {
+                    Some(c) if c.is_whitespace() => len += c.len_utf8(),
+                    _ => break,
                 }
             }
-        } else if in_multiline_comment {
-            while let Some(c) = chars.next() {
-                self.offset += c.len_utf8();
-                self.rest = &self.rest[c.len_utf8()..];
-                if c == '*' {
-                    if let Some(c) = chars.next() {
-                        self.offset += c.len_utf8();
-                        self.rest = &self.rest[c.len_utf8()..];
-                        if c == '/' {
-                            break;
-                        }
+        }
+
+        len
+    }
+
+    /// Skips a single comment, returns whether there was one to skip
+    fn skip_comment(&mut self) -> bool {
+        let start = self.offset;
+
+        if self.rest.starts_with("//") {
+            // The '\n' is left for `skip_whitespace` so the line gets recorded
+            let len = memchr(b'\n', self.rest.as_bytes()).unwrap_or(self.rest.len());
+            self.bump(len);
+            self.push_trivia(TriviaKind::LineComment, start);
+            true
+        } else if self.rest.starts_with("/*") {
+            self.bump(2);
+
+            loop {
+                let bytes = self.rest.as_bytes();
+
+                match memchr2(b'*', b'\n', bytes) {
+                    Some(i) if bytes[i] == b'\n' => {
+                        self.bump(i + 1);
+                        self.end_line(true);
+                    }
+                    Some(i) if bytes.get(i + 1) == Some(&b'/') => {
+                        self.bump(i + 2);
+                        break;
+                    }
+                    Some(i) => self.bump(i + 1),
+                    // Unterminated, so it goes on until the end of the input
+                    None => {
+                        self.bump(bytes.len());
+                        break;
                     }
                 }
             }
+
+            self.push_trivia(TriviaKind::BlockComment, start);
+            true
+        } else {
+            false
         }
-        chars.next()
     }
 
-    /// Consumes an identifier
+    /// Consumes an identifier or a keyword
     fn consume_ident(&mut self) {
-        let mut chars = self.rest.chars();
-
-        let c = chars.next().expect("This function should only be called when we still have at least one (alphabetical) char in the input");
         let start = self.offset;
+        let len = self
+            .rest
+            .bytes()
+            .position(|byte| !class::is_ident(byte))
+            .unwrap_or(self.rest.len());
+
+        let name = &self.rest[..len];
+        let keyword = Keyword::from_ident(name);
+        let token_source = TokenSource {
+            file: self.file,
+            span: self.span(start, start + len),
+            line: self.line(),
+        };
 
-        loop {
-            match chars.next() {
-                Some('A'..='Z' | 'a'..='z' | '1'..='9' | '_') => (),
-                Some(c) if c.is_whitespace() => break,
-                _ => break,
-            };
-            self.offset += c.len_utf8();
+        match keyword {
+            Some(keyword) => {
+                self.output
+                    .push_token(TokenType::Keyword(keyword), false, token_source)
+            }
+            None => self.output.push_ident(name, token_source),
         }
 
-        self.offset += c.len_utf8();
-        self.rest = &self.source[self.offset..];
+        self.bump(len);
+    }
 
-        let token_source = TokenSource {
-            start,
-            end: self.offset,
-            line: self.output.current_line(),
+    /// Consumes an integer or floating constant
+    fn consume_numeric_constant(&mut self) {
+        let start = self.offset;
+        let line = self.line();
+        let len = literal::pp_number_len(self.rest);
+        let text = &self.rest[..len];
+
+        let (ttype, has_error) = match literal::classify_number(text) {
+            Ok(ttype) => (ttype, false),
+            Err(kind) => {
+                error!("Invalid numeric constant '{text}': {kind}");
+                self.push_error(kind, start, start + len);
+                (literal::fallback_number_type(text), true)
+            }
         };
 
-        self.output
-            .push_token(TokenType::Ident, false, token_source);
-    }
+        self.bump(len);
 
-    fn consume_numeric_constant(&mut self) -> LexResult<()> {
-        let mut chars = self.rest.chars();
+        let token_source = self.token_source(start, line);
 
-        let c = chars.next().expect("This function should only be called when we still have at least one (alpha_numerical) char in the input");
+        self.output.push_token(ttype, has_error, token_source);
+    }
+
+    /// Consumes a char or string literal delimited by `quote`, decoding its escape sequences
+    fn consume_quoted_literal(&mut self, quote: char) {
         let start = self.offset;
+        let line = self.line();
 
-        // We allow '_' inside numbers
-        while let Some(c) = chars.next() {
-            match c {
-                '0'..='9' | '_' => {
-                    self.offset += c.len_utf8();
-                }
-                c if matches!(c, 'a'..='z' | 'A'..='Z') => {
-                    error!(
-                        "Invalid numeric constant: found '{}' while lexing a number",
-                        c
-                    );
-                    return Err(LexError::InvalidNumericConstant { c });
+        let ttype = if quote == '\'' {
+            TokenType::CharLiteral
+        } else {
+            TokenType::StringLiteral
+        };
+
+        let mut chars = self.rest.char_indices().skip(1);
+
+        // Literals can't span multiple lines, so a newline before the closing quote also means
+        // it's unterminated
+        let len = loop {
+            match chars.next() {
+                Some((_, '\\')) => match chars.next() {
+                    Some((i, '\n')) => break Err(i),
+                    None => break Err(self.rest.len()),
+                    Some(_) => (),
+                },
+                Some((i, c)) if c == quote => break Ok(i + 1),
+                Some((i, '\n')) => break Err(i),
+                None => break Err(self.rest.len()),
+                Some(_) => (),
+            }
+        };
+
+        let (len, bytes, has_error) = match len {
+            Ok(len) => match literal::decode_escapes(&self.rest[1..len - 1]) {
+                Ok(bytes) if bytes.is_empty() && ttype == TokenType::CharLiteral => {
+                    self.push_error(LexErrorKind::EmptyCharLiteral, start, start + len);
+                    (len, bytes, true)
                 }
-                _ => {
-                    break;
+                Ok(bytes) => (len, bytes, false),
+                Err((kind, range)) => {
+                    // The range is relative to the literal's contents, which start after the quote
+                    self.push_error(kind, start + 1 + range.start, start + 1 + range.end);
+                    (len, Vec::new(), true)
                 }
+            },
+            // Unterminated literals extend to the end of the line, but the error points at the
+            // opening quote since that's where the problem is most likely to be
+            Err(len) => {
+                let kind = if quote == '\'' {
+                    LexErrorKind::UnterminatedCharLiteral
+                } else {
+                    LexErrorKind::UnterminatedStringLiteral
+                };
+
+                self.push_error(kind, start, start + 1);
+                (len, Vec::new(), true)
             }
-        }
+        };
 
-        self.offset += c.len_utf8();
+        self.bump(len);
 
-        self.rest = &self.source[self.offset..];
+        let token_source = self.token_source(start, line);
 
-        let token_source = TokenSource {
-            start,
-            end: self.offset,
-            line: self.output.current_line(),
+        self.output
+            .push_literal(ttype, has_error, token_source, bytes);
+    }
+
+    /// Consumes a punctuator which is `len` bytes long
+    fn consume_punctuator(&mut self, ttype: TokenType, len: usize) {
+        let start = self.offset;
+        let line = self.line();
+
+        self.bump(len);
+
+        let token_source = self.token_source(start, line);
+
+        self.output.push_token(ttype, false, token_source);
+    }
+
+    /// Consumes a linemarker (`# 42 "file.c"`) or `#line` directive if there's one at the cursor,
+    /// returns whether there was one
+    ///
+    /// These are left by the preprocessor so positions can refer to the original files and lines.
+    /// Anything after the file name (like the flags in gcc's linemarkers) is ignored.
+    fn consume_line_marker(&mut self) -> bool {
+        if !self.line_markers || !self.output.at_line_start() {
+            return false;
+        }
+
+        let len = self.rest.find('\n').unwrap_or(self.rest.len());
+        let Some((line, file)) = literal::parse_line_marker(&self.rest[1..len]) else {
+            return false;
         };
 
+        let start = self.offset;
+        self.bump(len);
+        self.push_trivia(TriviaKind::LineMarker, start);
+
+        // The marker describes the line right after it, and files are kept until another one
+        // changes them
+        let file = file.or_else(|| self.output.last_line_marker().and_then(|m| m.file.clone()));
+        self.output.push_line_marker(LineMarker {
+            line: self.output.current_line() + 1,
+            presumed_line: line.saturating_sub(1),
+            file,
+        });
+
+        true
+    }
+
+    /// Consumes a char which can't start any token
+    fn consume_invalid_char(&mut self, c: char) {
+        let start = self.offset;
+        let line = self.line();
+
+        error!("Invalid char '{c}'");
+        self.push_error(LexErrorKind::InvalidChar { c }, start, start + c.len_utf8());
+
+        self.bump(c.len_utf8());
+
+        let token_source = self.token_source(start, line);
+
         self.output
-            .push_token(TokenType::Constant, false, token_source);
+            .push_token(TokenType::Unknown, true, token_source);
+    }
+
+    /// Records an error for the source text in [start, end)
+    fn push_error(&mut self, kind: LexErrorKind, start: usize, end: usize) {
+        let span = self.span(start, end);
+
+        self.output.push_error(LexError {
+            kind,
+            file: self.file,
+            span,
+        });
+    }
+
+    /// Lexes `source` on its own, failing with every error found if there's at least one
+    pub fn lex(source: &str) -> LexResult<TokenizedOutput> {
+        let (sources, file) = SourceMap::anonymous(source);
 
-        Ok(())
+        Self::lex_file(&sources, file)
     }
 
-    pub fn lex(source: &str) -> LexResult<TokenizedOutput<'_>> {
+    /// Lexes all of `source` on its own even if there are errors, see [`Lexer::tokenize`]
+    pub fn lex_all(source: &str) -> TokenizedOutput {
+        let (sources, file) = SourceMap::anonymous(source);
+
+        Lexer::new(&sources, file).tokenize()
+    }
+
+    /// Lexes `file`, failing with every error found if there's at least one
+    pub fn lex_file(sources: &SourceMap, file: FileId) -> LexResult<TokenizedOutput> {
+        let mut output = Lexer::new(sources, file).tokenize();
+
+        if output.errors().is_empty() {
+            Ok(output)
+        } else {
+            Err(LexErrors {
+                errors: output.take_errors(),
+            })
+        }
+    }
+
+    /// Lexes the rest of the file even if there are errors, tokens which couldn't be lexed
+    /// correctly have `has_error` set and the errors are available through
+    /// [`TokenizedOutput::errors`]
+    pub fn tokenize(mut self) -> TokenizedOutput {
         let _ = span!(Level::TRACE, "Lexing").entered();
 
-        let mut lexer = Self::new(source);
+        self.run_lexer();
 
-        lexer.run_lexer()?;
+        self.output
+    }
 
-        Ok(lexer.output)
+    fn run_lexer(&mut self) {
+        while self.lex_token() {}
     }
 
-    fn run_lexer(&mut self) -> LexResult<()> {
-        while let Some(c) = self.skip_whitespace() {
-            let c_offset = self.offset;
-            let c_str = &self.rest[..c.len_utf8()];
-            let mut emit_single_char_token = |ttype: TokenType| {
-                self.offset += c_str.len();
-                self.rest = &self.rest[c_str.len()..];
-                self.output.push_token(
-                    ttype,
-                    false,
-                    TokenSource {
-                        start: c_offset,
-                        end: c_offset + c.len_utf8(),
-                        line: self.output.current_line(),
-                    },
-                );
-            };
+    /// Lexes the next token, returns whether there was one before the end of the input
+    fn lex_token(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
 
-            match c {
-                '(' => emit_single_char_token(TokenType::OpenParen),
-                ')' => emit_single_char_token(TokenType::CloseParen),
-                '{' => emit_single_char_token(TokenType::OpenBrace),
-                '}' => emit_single_char_token(TokenType::CloseBrace),
-                ';' => emit_single_char_token(TokenType::Semicolon),
-                '/' => emit_single_char_token(TokenType::FrontSlash),
-                '-' => emit_single_char_token(TokenType::Hyphen),
-                ':' => emit_single_char_token(TokenType::Colon),
-                '*' => emit_single_char_token(TokenType::Asterisk),
-                '\'' => emit_single_char_token(TokenType::Quote),
-                '"' => emit_single_char_token(TokenType::DoubleQuote),
-                ',' => emit_single_char_token(TokenType::Comma),
-                'a'..='z' | 'A'..='Z' | '_' => self.consume_ident(),
-                c if c.is_ascii_alphanumeric() => self.consume_numeric_constant()?,
-                '\0' => break,
-                c => return Err(LexError::InvalidChar { c }),
+        while let Some(byte) = self.skip_whitespace() {
+            match byte {
+                _ if class::is_ident_start(byte) => self.consume_ident(),
+                b'0'..=b'9' => self.consume_numeric_constant(),
+                b'.' if self.rest.as_bytes().get(1).is_some_and(u8::is_ascii_digit) => {
+                    self.consume_numeric_constant()
+                }
+                b'\'' | b'"' => self.consume_quoted_literal(byte as char),
+                b'#' if self.consume_line_marker() => continue,
+                b'\0' => {
+                    // The rest of the input isn't lexed, but it's still part of the source
+                    let start = self.offset;
+                    self.bump(self.rest.len());
+                    self.push_trivia(TriviaKind::Ignored, start);
+                    break;
+                }
+                _ => match token::punctuator(self.rest) {
+                    Some((punctuator, ttype)) => self.consume_punctuator(ttype, punctuator.len()),
+                    None => self.consume_invalid_char(self.rest.chars().next().unwrap()),
+                },
             };
+
+            return true;
         }
 
+        self.finish();
+        false
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+
         // Emit last line since it doesn't (necessarily) have a '\n'
         self.output.push_line(Line {
             start: self.output.current_line_offset(),
-            end: self.offset,
+            end: self.original(self.offset),
+            ends_in_comment: false,
+            ends_in_splice: false,
         });
 
-        Ok(())
+        if self.output.keeps_trivia() {
+            self.output.attach_end_trivia();
+        }
     }
 }
 
-mod output {
-    use std::fmt::{self, Display};
+/// Lexes one token at a time, failing with the errors found in a token if there are any
+///
+/// Tokens are still recorded in [`Lexer::output`], which is where their text and values are.
+impl Iterator for Lexer<'_> {
+    type Item = LexResult<Token>;
 
-    use crate::{Token, TokenType, line::Line, token::TokenSource};
+    fn next(&mut self) -> Option<Self::Item> {
+        let errors = self.output.errors().len();
+
+        if !self.lex_token() {
+            return None;
+        }
+
+        let token = *self.output.tokens().last().unwrap();
+        let errors = &self.output.errors()[errors..];
+
+        if errors.is_empty() {
+            Some(Ok(token))
+        } else {
+            Some(Err(LexErrors {
+                errors: errors.to_vec(),
+            }))
+        }
+    }
+}
+
+mod output {
+    use std::{
+        collections::BTreeMap,
+        fmt::{self, Display},
+        ops::Range,
+        path::Path,
+    };
+
+    use crate::{
+        LexError, Token, TokenType,
+        line::{Line, LineMarker, Position},
+        literal,
+        source_map::{FileId, SourceMap},
+        symbol::{Symbol, SymbolTable},
+        token::TokenSource,
+        trivia::{TokenTrivia, Trivia, TriviaKind},
+    };
 
     #[derive(Debug, Clone)]
-    pub struct TokenizedOutput<'src> {
-        source: &'src str,
+    pub struct TokenizedOutput {
+        sources: SourceMap,
+        /// The file which was lexed, which lines and linemarkers refer to
+        file: FileId,
         tokens: Vec<Token>,
         token_sources: Vec<TokenSource>,
         // We don't need a Vec<LineHandle> since they're a simple range [0..lines.len()]
         lines: Vec<Line>,
+        /// Decoded values of char and string literals, indexed by token handle
+        literals: BTreeMap<usize, Vec<u8>>,
+        /// Interned names of identifiers along with their token handle, sorted by handle
+        idents: Vec<(usize, Symbol)>,
+        symbols: SymbolTable,
+        /// Linemarkers and `#line` directives found, sorted by the line they start at
+        line_markers: Vec<LineMarker>,
+        errors: Vec<LexError>,
+        /// Whether whitespace and comments are kept, see [`crate::Lexer::keep_trivia`]
+        keep_trivia: bool,
+        /// Every piece of trivia in source order
+        trivia: Vec<Trivia>,
+        /// Trivia owned by each token, indexed by token handle
+        token_trivia: Vec<TokenTrivia>,
+        /// Trivia after the last token's line, which no token owns
+        end_trivia: Range<usize>,
     }
 
-    impl<'src> TokenizedOutput<'src> {
-        pub(crate) fn new(source: &'src str) -> Self {
+    impl TokenizedOutput {
+        pub(crate) fn new(sources: SourceMap, file: FileId) -> Self {
             TokenizedOutput {
-                source,
+                sources,
+                file,
                 tokens: Vec::new(),
                 token_sources: Vec::new(),
                 lines: Vec::new(),
+                literals: BTreeMap::new(),
+                idents: Vec::new(),
+                symbols: SymbolTable::new(),
+                line_markers: Vec::new(),
+                errors: Vec::new(),
+                keep_trivia: false,
+                trivia: Vec::new(),
+                token_trivia: Vec::new(),
+                end_trivia: 0..0,
             }
         }
 
@@ -275,8 +656,100 @@ mod output {
             *self.token_sources.get(handle).unwrap()
         }
 
-        pub fn token_text(&self, handle: usize) -> &'src str {
-            self.token_source(handle).fmt(self.source)
+        /// Spelling of a token, which is its text with trigraphs replaced and line splices removed
+        pub fn token_text(&self, handle: usize) -> &str {
+            let source = self.token_source(handle);
+            self.sources.spelling(source.file, source.span)
+        }
+
+        pub fn sources(&self) -> &SourceMap {
+            &self.sources
+        }
+
+        pub fn file(&self) -> FileId {
+            self.file
+        }
+
+        /// Value of an integer constant token, malformed constants have a value of 0
+        ///
+        /// Panics if the token is not an `IntConstant`
+        pub fn int_value(&self, handle: usize) -> u64 {
+            match self.tokens[handle] {
+                Token {
+                    ttype: TokenType::IntConstant { .. },
+                    has_error: true,
+                    ..
+                } => 0,
+                Token {
+                    ttype: TokenType::IntConstant { radix, .. },
+                    ..
+                } => literal::int_value(self.token_text(handle), radix),
+                token => panic!("Expected [IntConstant], got [{}]", token.ttype),
+            }
+        }
+
+        /// Value of a floating constant token, malformed constants have a value of 0
+        ///
+        /// Panics if the token is not a `FloatConstant`
+        pub fn float_value(&self, handle: usize) -> f64 {
+            match self.tokens[handle] {
+                Token {
+                    ttype: TokenType::FloatConstant { .. },
+                    has_error: true,
+                    ..
+                } => 0.0,
+                Token {
+                    ttype: TokenType::FloatConstant { radix, .. },
+                    ..
+                } => literal::float_value(self.token_text(handle), radix),
+                token => panic!("Expected [FloatConstant], got [{}]", token.ttype),
+            }
+        }
+
+        /// Value of a char literal token, which is an `int` in C
+        ///
+        /// Panics if the token is not a `CharLiteral`
+        pub fn char_value(&self, handle: usize) -> i32 {
+            match self.tokens[handle].ttype {
+                TokenType::CharLiteral => literal::char_value(&self.literals[&handle]),
+                ttype => panic!("Expected [CharLiteral], got [{ttype}]"),
+            }
+        }
+
+        /// Decoded bytes of a string literal token, without the terminating NUL
+        ///
+        /// Panics if the token is not a `StringLiteral`
+        pub fn string_value(&self, handle: usize) -> &[u8] {
+            match self.tokens[handle].ttype {
+                TokenType::StringLiteral => &self.literals[&handle],
+                ttype => panic!("Expected [StringLiteral], got [{ttype}]"),
+            }
+        }
+
+        /// Interned name of an identifier token
+        ///
+        /// Panics if the token is not an `Ident`
+        pub fn symbol(&self, handle: usize) -> Symbol {
+            match self.tokens[handle].ttype {
+                TokenType::Ident => {
+                    let i = self
+                        .idents
+                        .binary_search_by_key(&handle, |&(handle, _)| handle)
+                        .unwrap();
+                    self.idents[i].1
+                }
+                ttype => panic!("Expected [Ident], got [{ttype}]"),
+            }
+        }
+
+        /// Names of every identifier lexed
+        pub fn symbols(&self) -> &SymbolTable {
+            &self.symbols
+        }
+
+        /// Lets later stages intern names of their own, like the unique names of variables
+        pub fn symbols_mut(&mut self) -> &mut SymbolTable {
+            &mut self.symbols
         }
 
         pub fn tokens(&self) -> &[Token] {
@@ -289,6 +762,14 @@ mod output {
             has_error: bool,
             source: TokenSource,
         ) {
+            if self.keep_trivia {
+                let leading = self.attach_trivia();
+                self.token_trivia.push(TokenTrivia {
+                    trailing: leading.end..leading.end,
+                    leading,
+                });
+            }
+
             self.tokens.push(Token {
                 ttype,
                 has_error,
@@ -297,6 +778,184 @@ mod output {
             self.token_sources.push(source);
         }
 
+        /// Pushes a char or string literal token along with its decoded value
+        pub(crate) fn push_literal(
+            &mut self,
+            ttype: TokenType,
+            has_error: bool,
+            source: TokenSource,
+            bytes: Vec<u8>,
+        ) {
+            self.literals.insert(self.token_sources.len(), bytes);
+            self.push_token(ttype, has_error, source);
+        }
+
+        /// Pushes an identifier token, interning its name
+        pub(crate) fn push_ident(&mut self, name: &str, source: TokenSource) {
+            let symbol = self.symbols.intern(name);
+            self.idents.push((self.token_sources.len(), symbol));
+            self.push_token(TokenType::Ident, false, source);
+        }
+
+        /// Line and column of a byte offset into the lexed file
+        pub fn position(&self, offset: usize) -> Position {
+            self.sources.position(self.file, offset)
+        }
+
+        /// File and line of a byte offset into the lexed file as given by linemarkers, which is
+        /// where the code was originally written before being preprocessed
+        ///
+        /// The file is the lexed one until a linemarker names another.
+        pub fn presumed_position(&self, offset: usize) -> (&Path, Position) {
+            let position = self.position(offset);
+            let marker = self
+                .line_markers
+                .partition_point(|marker| marker.line <= position.line)
+                .checked_sub(1)
+                .map(|i| &self.line_markers[i]);
+
+            let path = marker
+                .and_then(|marker| marker.file.as_deref())
+                .map_or(self.sources.path(self.file), Path::new);
+
+            match marker {
+                Some(marker) => (
+                    path,
+                    Position {
+                        line: marker.presumed_line + (position.line - marker.line),
+                        ..position
+                    },
+                ),
+                None => (path, position),
+            }
+        }
+
+        /// Trivia between the previous token's line and this token
+        ///
+        /// Always empty unless lexed with [`crate::Lexer::keep_trivia`], as is every other trivia
+        /// accessor.
+        pub fn leading_trivia(&self, handle: usize) -> &[Trivia] {
+            self.token_trivia
+                .get(handle)
+                .map_or(&[], |trivia| &self.trivia[trivia.leading.clone()])
+        }
+
+        /// Trivia after this token up to the end of its line, not including the '\n'
+        pub fn trailing_trivia(&self, handle: usize) -> &[Trivia] {
+            self.token_trivia
+                .get(handle)
+                .map_or(&[], |trivia| &self.trivia[trivia.trailing.clone()])
+        }
+
+        /// Trivia after the last token's line, which is all of it if there are no tokens
+        pub fn end_trivia(&self) -> &[Trivia] {
+            &self.trivia[self.end_trivia.clone()]
+        }
+
+        pub fn trivia_text(&self, trivia: &Trivia) -> &str {
+            self.sources.text(self.file, trivia.span)
+        }
+
+        /// Source text rebuilt from the tokens and their trivia, which is exactly the lexed file
+        /// when trivia is kept
+        pub fn reconstruct_source(&self) -> String {
+            let mut source = String::with_capacity(self.sources.source(self.file).len());
+            let push_trivia = |source: &mut String, trivia: &[Trivia]| {
+                for trivia in trivia {
+                    source.push_str(self.trivia_text(trivia));
+                }
+            };
+
+            for token in &self.tokens {
+                push_trivia(&mut source, self.leading_trivia(token.handle));
+                let token_source = self.token_source(token.handle);
+                source.push_str(self.sources.text(token_source.file, token_source.span));
+                push_trivia(&mut source, self.trailing_trivia(token.handle));
+            }
+            push_trivia(&mut source, self.end_trivia());
+
+            source
+        }
+
+        pub(crate) fn keep_trivia(&mut self) {
+            self.keep_trivia = true;
+        }
+
+        pub(crate) fn keeps_trivia(&self) -> bool {
+            self.keep_trivia
+        }
+
+        pub(crate) fn push_trivia(&mut self, trivia: Trivia) {
+            self.trivia.push(trivia);
+        }
+
+        /// Gives the trivia since the last token up to its first newline to that token, returning
+        /// the range of what's left
+        fn attach_trivia(&mut self) -> Range<usize> {
+            let Some(previous) = self.token_trivia.last_mut() else {
+                return 0..self.trivia.len();
+            };
+
+            let start = previous.leading.end;
+            let split = self.trivia[start..]
+                .iter()
+                .position(|trivia| trivia.kind == TriviaKind::Newline)
+                .map_or(self.trivia.len(), |i| start + i);
+            previous.trailing = start..split;
+
+            split..self.trivia.len()
+        }
+
+        pub(crate) fn attach_end_trivia(&mut self) {
+            self.end_trivia = self.attach_trivia();
+        }
+
+        /// Every line of the lexed file, including the ones before where a restarted lexer started
+        pub fn lines(&self) -> &[Line] {
+            &self.lines
+        }
+
+        /// Starts over at `line`, keeping the lines and linemarkers of `previous` before it
+        pub(crate) fn restart_at(&mut self, previous: &TokenizedOutput, line: usize) {
+            self.lines = previous.lines[..line].to_vec();
+            // Names keep the same symbols as before restarting
+            self.symbols = previous.symbols.clone();
+            self.line_markers = previous
+                .line_markers
+                .iter()
+                .take_while(|marker| marker.line <= line)
+                .cloned()
+                .collect();
+        }
+
+        /// Errors found while lexing, in the order they appear in the source
+        pub fn errors(&self) -> &[LexError] {
+            &self.errors
+        }
+
+        pub(crate) fn push_error(&mut self, error: LexError) {
+            self.errors.push(error);
+        }
+
+        pub(crate) fn take_errors(&mut self) -> Vec<LexError> {
+            std::mem::take(&mut self.errors)
+        }
+
+        pub(crate) fn push_line_marker(&mut self, marker: LineMarker) {
+            self.line_markers.push(marker);
+        }
+
+        pub(crate) fn last_line_marker(&self) -> Option<&LineMarker> {
+            self.line_markers.last()
+        }
+
+        /// Whether no token has been lexed in the current line yet
+        pub(crate) fn at_line_start(&self) -> bool {
+            self.token_sources
+                .last()
+                .is_none_or(|source| source.line != self.current_line())
+        }
+
         pub(crate) fn push_line(&mut self, line: Line) {
             self.lines.push(line);
         }
@@ -318,15 +977,18 @@ mod output {
         }
     }
 
-    impl<'src> Display for TokenizedOutput<'src> {
+    impl Display for TokenizedOutput {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            for (token, source) in self.tokens.iter().zip(self.token_sources.iter()) {
+            for token in &self.tokens {
+                let (path, position) =
+                    self.presumed_position(self.token_source(token.handle).span.start);
+
                 writeln!(
                     f,
-                    "{}: [{}] \"{}\"",
-                    source.line,
+                    "{}:{position}: [{}] \"{}\"",
+                    path.display(),
                     token.ttype,
-                    source.fmt(self.source)
+                    self.token_text(token.handle)
                 )?;
             }
             Ok(())
@@ -336,7 +998,13 @@ mod output {
 
 #[cfg(test)]
 mod tests {
-    use crate::{LexError, Lexer, TokenType};
+    use crate::{
+        LexErrorKind, LexErrors, Lexer, TokenType,
+        line::Position,
+        source_map::SourceMap,
+        token::{Keyword, PUNCTUATORS, Span},
+        trivia::{Trivia, TriviaKind},
+    };
 
     macro_rules! snapshot_test (
         ($string:expr) => {
@@ -381,10 +1049,511 @@ mod tests {
         );
     }
 
+    #[test]
+    fn keywords() {
+        snapshot_test!("int main(void) { return 0; }");
+    }
+
+    #[test]
+    fn all_keywords() {
+        let source = Keyword::ALL.map(|keyword| keyword.as_str()).join(" ");
+        let output = Lexer::lex(&source).unwrap();
+
+        assert_eq!(output.len(), Keyword::ALL.len());
+
+        for (token, keyword) in output.tokens().iter().zip(Keyword::ALL) {
+            assert_eq!(token.ttype, TokenType::Keyword(keyword));
+        }
+    }
+
+    #[test]
+    fn keyword_prefixed_idents() {
+        let source = "integer returns _Bool1 If";
+        let output = Lexer::lex(source).unwrap();
+
+        assert_eq!(output.len(), 4);
+        assert!(output.tokens().iter().all(|t| t.ttype == TokenType::Ident));
+    }
+
+    #[test]
+    fn interned_idents() {
+        let output = Lexer::lex("x = y + x; int main").unwrap();
+        let symbols = output.symbols();
+
+        assert_eq!(output.symbol(0), output.symbol(4));
+        assert_ne!(output.symbol(0), output.symbol(2));
+        assert_eq!(&symbols[output.symbol(7)], "main");
+        // Keywords aren't interned
+        assert_eq!(symbols.len(), 3);
+    }
+
+    #[test]
+    fn punctuators() {
+        snapshot_test!(
+            "[](){}.->++--&*+-~!/%<<>><><=>===!=^|&&||?:;...=*=/=%=+=-=<<=>>=&=^=|=,###"
+        );
+    }
+
+    #[test]
+    fn maximal_munch() {
+        snapshot_test!("a+++++b x<<=y>>z ....");
+    }
+
+    #[test]
+    fn division_is_not_a_comment() {
+        snapshot_test!("a / b // comment\n/* multi\nline */ c /= d");
+    }
+
+    #[test]
+    fn every_punctuator() {
+        for (punctuator, ttype) in PUNCTUATORS {
+            let output = Lexer::lex(punctuator).unwrap();
+
+            assert_eq!(output.len(), 1, "{punctuator} should be a single token");
+            assert_eq!(output.get(0).unwrap().ttype, ttype);
+        }
+    }
+
+    #[test]
+    fn numeric_constants() {
+        snapshot_test!(
+            "0 42 0xFF 0755 10u 10l 10ul 10LLU 1.5e-3 .5 1e10 1. 0x1p-2 0x.8P+1 1.5f 2.0L"
+        );
+    }
+
+    #[test]
+    fn numeric_constant_values() {
+        let source = "0 42 0xFF 0755 18446744073709551615ull 1.5e-3 .5 1e10 0x1p-2 0x1.8p1";
+        let output = Lexer::lex(source).unwrap();
+
+        let ints: Vec<u64> = (0..5).map(|i| output.int_value(i)).collect();
+        assert_eq!(ints, [0, 42, 255, 0o755, u64::MAX]);
+
+        let floats: Vec<f64> = (5..10).map(|i| output.float_value(i)).collect();
+        assert_eq!(floats, [1.5e-3, 0.5, 1e10, 0.25, 3.0]);
+    }
+
+    #[test]
+    fn malformed_numeric_constants() {
+        let cases = [
+            ("1.2.3", "Too many decimal points in numeric constant"),
+            ("1.0e10.0", "Too many decimal points in numeric constant"),
+            ("0x", "Expected hexadecimal digits after '0x' prefix"),
+            ("0x.p1", "Expected hexadecimal digits after '0x' prefix"),
+            ("30.e", "Exponent has no digits"),
+            ("24e-", "Exponent has no digits"),
+            (
+                "0x1.8",
+                "Hexadecimal floating constant requires an exponent",
+            ),
+            ("089", "Invalid digit '8' in octal constant"),
+            ("0uu", "Invalid suffix 'uu' on numeric constant"),
+            ("0lL", "Invalid suffix 'lL' on numeric constant"),
+            ("1.5u", "Invalid char in numeric constant 'u'"),
+            ("1E2x", "Invalid char in numeric constant 'x'"),
+            ("2._", "Invalid char in numeric constant '_'"),
+            ("18446744073709551616", "Integer constant is too large"),
+        ];
+
+        for (source, expected) in cases {
+            match Lexer::lex(source) {
+                Err(e) => assert_eq!(e.to_string(), expected, "while lexing {source}"),
+                Ok(_) => panic!("Expected {source} to fail with '{expected}'"),
+            }
+        }
+    }
+
+    #[test]
+    fn char_and_string_literals() {
+        snapshot_test!(r#"'a' '\n' "hello\n" "" "say \"hi\"""#);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let source = r#"'a' '\'' '\101' '\x41' '\0' '\377' 'ab' "\a\b\f\n\r\t\v\?\\" "\x41\101\u00e9\U0001F600" "é""#;
+        let output = Lexer::lex(source).unwrap();
+
+        let chars: Vec<i32> = (0..7).map(|i| output.char_value(i)).collect();
+        assert_eq!(chars, [97, 39, 65, 65, 0, -1, 0x6162]);
+
+        assert_eq!(output.string_value(7), b"\x07\x08\x0C\n\r\t\x0B?\\");
+        assert_eq!(output.string_value(8), "AAé😀".as_bytes());
+        assert_eq!(output.string_value(9), "é".as_bytes());
+    }
+
+    #[test]
+    fn malformed_literals() {
+        let cases = [
+            (r"'\y'", r"Invalid escape sequence '\y'"),
+            (r#""foo\ybar""#, r"Invalid escape sequence '\y'"),
+            (r"'\xg'", r"Escape sequence '\x' is missing hex digits"),
+            (r"'\u12'", r"Escape sequence '\u' is missing hex digits"),
+            (r"'\x100'", "Escape sequence out of range"),
+            (r"'\777'", "Escape sequence out of range"),
+            (r"'\u0041'", r"Invalid universal character '\U00000041'"),
+            (r"'\uD800'", r"Invalid universal character '\U0000D800'"),
+            ("''", "Empty char literal"),
+        ];
+
+        for (source, expected) in cases {
+            match Lexer::lex(source) {
+                Err(e) => assert_eq!(e.to_string(), expected, "while lexing {source}"),
+                Ok(_) => panic!("Expected {source} to fail with '{expected}'"),
+            }
+        }
+    }
+
+    #[test]
+    fn unterminated_literals() {
+        let cases = [
+            ("return 'x\n}", LexErrorKind::UnterminatedCharLiteral, 7),
+            (r"x = '\';", LexErrorKind::UnterminatedCharLiteral, 4),
+            (
+                "p = \"hello\n world\";",
+                LexErrorKind::UnterminatedStringLiteral,
+                4,
+            ),
+            (r#"p = "foo\";"#, LexErrorKind::UnterminatedStringLiteral, 4),
+        ];
+
+        for (source, kind, start) in cases {
+            let errors = Lexer::lex(source).unwrap_err().errors;
+
+            assert_eq!(errors[0].kind, kind, "while lexing {source:?}");
+            // Points at the opening quote
+            assert_eq!(
+                errors[0].span,
+                Span {
+                    start,
+                    end: start + 1
+                },
+                "while lexing {source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let source = "int main(void) {\n    return 1foo @ '\\q';\n    \"abc\n}";
+        let output = Lexer::lex_all(source);
+
+        let errors: Vec<_> = output
+            .errors()
+            .iter()
+            .map(|e| (e.kind.clone(), e.span.start, e.span.end))
+            .collect();
+
+        assert_eq!(
+            errors,
+            [
+                (LexErrorKind::InvalidNumericConstant { c: 'f' }, 28, 32),
+                (LexErrorKind::InvalidChar { c: '@' }, 33, 34),
+                (LexErrorKind::InvalidEscape { c: 'q' }, 36, 38),
+                (LexErrorKind::UnterminatedStringLiteral, 45, 46),
+            ]
+        );
+
+        let positions: Vec<_> = output
+            .errors()
+            .iter()
+            .map(|e| output.position(e.span.start).to_string())
+            .collect();
+
+        assert_eq!(positions, ["2:12", "2:17", "2:20", "3:5"]);
+    }
+
+    #[test]
+    fn positions() {
+        let source = "a\n\n  bc // comment\n/* multi\nline */ \"é\" f\n";
+        let output = Lexer::lex(source).unwrap();
+
+        let positions: Vec<_> = output
+            .tokens()
+            .iter()
+            .map(|t| output.position(output.token_source(t.handle).span.start))
+            .collect();
+
+        assert_eq!(
+            positions,
+            [
+                Position { line: 0, column: 0 },
+                Position { line: 2, column: 2 },
+                Position { line: 4, column: 8 },
+                Position {
+                    line: 4,
+                    column: 12
+                },
+            ]
+        );
+
+        // The end of the input is on the empty line after the last '\n'
+        assert_eq!(
+            output.position(source.len()),
+            Position { line: 5, column: 0 }
+        );
+    }
+
+    #[test]
+    fn error_tokens() {
+        insta::assert_debug_snapshot!(Lexer::lex_all("return 1.2.3 ` '' x;"));
+    }
+
+    #[test]
+    fn line_markers() {
+        let source =
+            "int a;\n# 10 \"foo.h\" 1 3\nint b;\n\n#line 42\n  c\n#line 7 \"dir\\\\bar.c\"\nd";
+        let output = Lexer::lex(source).unwrap();
+
+        // Markers aren't tokens
+        let texts: Vec<_> = (0..output.len()).map(|i| output.token_text(i)).collect();
+        assert_eq!(texts, ["int", "a", ";", "int", "b", ";", "c", "d"]);
+
+        let presumed = |token: usize| {
+            let (file, position) = output.presumed_position(output.token_source(token).span.start);
+            (file.to_str().unwrap(), position.to_string())
+        };
+
+        assert_eq!(presumed(0), ("<input>", "1:1".to_string()));
+        assert_eq!(presumed(3), ("foo.h", "10:1".to_string()));
+        // The file is kept until another marker changes it
+        assert_eq!(presumed(6), ("foo.h", "42:3".to_string()));
+        assert_eq!(presumed(7), ("dir\\bar.c", "7:1".to_string()));
+
+        // Physical positions are unchanged
+        assert_eq!(
+            output.position(output.token_source(7).span.start),
+            Position { line: 7, column: 0 }
+        );
+    }
+
+    #[test]
+    fn not_line_markers() {
+        for source in [
+            "#define X 1",
+            "#line",
+            "#line x",
+            "# 1 \"unterminated",
+            "# 1 \"file\" flag",
+            "x # 1",
+        ] {
+            let output = Lexer::lex_all(source);
+
+            assert!(
+                output.tokens().iter().any(|t| t.ttype == TokenType::Hash),
+                "'{source}' isn't a linemarker"
+            );
+        }
+
+        let (sources, file) = SourceMap::anonymous("# 1 \"file.c\"\n#line 2");
+        let output = Lexer::new(&sources, file).keep_line_markers().tokenize();
+        assert_eq!(output.len(), 6);
+    }
+
+    #[test]
+    fn multiple_files() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file("main.c", "int main(void) {\n  return x;\n}");
+        let header = sources.add_file("x.h", "\nint x;");
+
+        let main_tokens = Lexer::lex_file(&sources, main).unwrap();
+        let header_tokens = Lexer::lex_file(&sources, header).unwrap();
+
+        assert_eq!(main_tokens.token_source(7).file, main);
+        assert_eq!(main_tokens.token_text(7), "x");
+        assert_eq!(header_tokens.token_source(1).file, header);
+        assert_eq!(header_tokens.token_text(1), "x");
+
+        assert_eq!(
+            header_tokens.to_string(),
+            "x.h:2:1: [Keyword(int)] \"int\"\nx.h:2:5: [Ident] \"x\"\nx.h:2:6: [Semicolon] \";\"\n"
+        );
+
+        // Errors point at the file they were found in
+        let bad = sources.add_file("bad.c", "@");
+        let errors = Lexer::lex_file(&sources, bad).unwrap_err().errors;
+        assert_eq!(errors[0].file, bad);
+    }
+
+    #[test]
+    fn trivia() {
+        let source = "  a /* one */ b // two\n\n  // three\nc;\n";
+        let (sources, file) = SourceMap::anonymous(source);
+        let output = Lexer::new(&sources, file).keep_trivia().tokenize();
+
+        let texts = |trivia: &[Trivia]| -> Vec<_> {
+            trivia.iter().map(|t| output.trivia_text(t)).collect()
+        };
+
+        assert_eq!(texts(output.leading_trivia(0)), ["  "]);
+        assert_eq!(texts(output.trailing_trivia(0)), [" ", "/* one */", " "]);
+        assert_eq!(texts(output.leading_trivia(1)), Vec::<&str>::new());
+        assert_eq!(texts(output.trailing_trivia(1)), [" ", "// two"]);
+        assert_eq!(
+            texts(output.leading_trivia(2)),
+            ["\n", "\n", "  ", "// three", "\n"]
+        );
+        assert_eq!(texts(output.end_trivia()), ["\n"]);
+
+        let kinds: Vec<_> = output.trailing_trivia(1).iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TriviaKind::Whitespace, TriviaKind::LineComment]);
+
+        // Trivia is opt-in
+        let output = Lexer::lex(source).unwrap();
+        assert!(output.leading_trivia(0).is_empty());
+        assert!(output.end_trivia().is_empty());
+    }
+
+    #[test]
+    fn lossless() {
+        for source in [
+            "",
+            "  \n\t",
+            "int main(void) {\r\n    return 0; // done\r\n}\r\n",
+            "a /* multi\nline */ b /* unterminated",
+            "x = 1foo @ '\\q' \"unterminated\n;",
+            "# 10 \"file.h\"\nint x;\n#line 3\ny",
+            "a\0 ignored \n text",
+            "\u{a0}é = \"ü\";",
+            "\\\nin\\\nt x??( /* \\\n */ y\\",
+        ] {
+            let (sources, file) = SourceMap::anonymous(source);
+            let output = Lexer::new(&sources, file).keep_trivia().tokenize();
+
+            assert_eq!(output.reconstruct_source(), source);
+        }
+    }
+
+    #[test]
+    fn iterator() {
+        let (sources, file) = SourceMap::anonymous("x = 1a;\n# 5 \"f.c\"\ny");
+        let mut lexer = Lexer::new(&sources, file);
+
+        let first = lexer.next().unwrap().unwrap();
+        assert_eq!(first.ttype, TokenType::Ident);
+        assert_eq!(lexer.output().token_text(first.handle), "x");
+        assert_eq!(lexer.output().len(), 1);
+
+        let rest: Vec<_> = lexer.by_ref().collect();
+        assert_eq!(rest.len(), 4);
+        assert!(rest[0].is_ok());
+        assert_eq!(
+            rest[1].as_ref().unwrap_err().errors[0].kind,
+            LexErrorKind::InvalidNumericConstant { c: 'a' }
+        );
+        assert_eq!(rest[3].as_ref().unwrap().ttype, TokenType::Ident);
+        assert!(lexer.next().is_none());
+
+        // Everything pulled is in the output
+        assert_eq!(lexer.output().len(), 5);
+        assert_eq!(lexer.output().errors().len(), 1);
+        assert_eq!(lexer.output().lines().len(), 3);
+    }
+
+    #[test]
+    fn line_splices() {
+        let source = "in\\\nt x = \"a\\\nb\";\\\n y\n/* c \\\n */ z";
+        let (sources, file) = SourceMap::anonymous(source);
+        let output = Lexer::lex_file(&sources, file).unwrap();
+
+        let texts: Vec<_> = (0..output.len()).map(|i| output.token_text(i)).collect();
+        assert_eq!(texts, ["int", "x", "=", "\"ab\"", ";", "y", "z"]);
+        assert_eq!(
+            output.get(0).unwrap().ttype,
+            TokenType::Keyword(Keyword::Int)
+        );
+        assert_eq!(output.string_value(3), b"ab");
+
+        // Positions are still on the physical lines
+        assert_eq!(output.token_source(0).span, Span { start: 0, end: 5 });
+        let lines: Vec<_> = (0..output.len())
+            .map(|i| output.token_source(i).line)
+            .collect();
+        assert_eq!(lines, [0, 1, 1, 1, 2, 3, 5]);
+        assert_eq!(output.lines().len(), 6);
+        assert_eq!(output.position(output.token_source(5).span.start).line, 3);
+
+        // Lines joined to the previous one can't be restarted at
+        let restarted = Lexer::new(&sources, file).restart_at(&output, 2);
+        assert_eq!(restarted.line(), 0);
+        let mut restarted = Lexer::new(&sources, file).restart_at(&output, 5);
+        assert_eq!(restarted.line(), 4);
+        let token = restarted.next().unwrap().unwrap();
+        assert_eq!(restarted.output().token_text(token.handle), "z");
+        assert!(restarted.next().is_none());
+    }
+
+    #[test]
+    fn trigraphs_and_digraphs() {
+        let output =
+            Lexer::lex("??=x <: :> <% %> %: %:%: ??( ??) ??< ??> ??! ??' ??- ?? ?").unwrap();
+        let ttypes: Vec<_> = output.tokens().iter().map(|token| token.ttype).collect();
+
+        assert_eq!(
+            ttypes,
+            [
+                TokenType::Hash,
+                TokenType::Ident,
+                TokenType::OpenBracket,
+                TokenType::CloseBracket,
+                TokenType::OpenBrace,
+                TokenType::CloseBrace,
+                TokenType::Hash,
+                TokenType::HashHash,
+                TokenType::OpenBracket,
+                TokenType::CloseBracket,
+                TokenType::OpenBrace,
+                TokenType::CloseBrace,
+                TokenType::Pipe,
+                TokenType::Caret,
+                TokenType::Tilde,
+                TokenType::Question,
+                TokenType::Question,
+                TokenType::Question,
+            ]
+        );
+        assert_eq!(output.token_text(0), "#");
+        assert_eq!(output.token_source(0).span, Span { start: 0, end: 3 });
+        assert_eq!(output.token_source(1).span, Span { start: 3, end: 4 });
+    }
+
+    #[test]
+    fn restart() {
+        let source = "a b\n# 10 \"f.c\"\nc /* one\ntwo */ d\ne";
+        let (mut sources, file) = SourceMap::anonymous(source);
+        let full = Lexer::lex(source).unwrap();
+
+        let restarted = Lexer::new(&sources, file).restart_at(&full, 4).tokenize();
+        assert_eq!(restarted.len(), 1);
+        assert_eq!(restarted.token_text(0), "e");
+        assert_eq!(restarted.token_source(0).line, 4);
+        let (path, position) = restarted.presumed_position(restarted.token_source(0).span.start);
+        assert_eq!((path.to_str().unwrap(), position.line), ("f.c", 11));
+
+        // Line 3 starts inside a comment, so lexing starts at the line the comment starts in
+        let mut lexer = Lexer::new(&sources, file).restart_at(&full, 3);
+        assert_eq!(lexer.line(), 2);
+        let handles: Vec<_> = lexer.by_ref().map(|token| token.unwrap().handle).collect();
+        let texts: Vec<_> = handles
+            .into_iter()
+            .map(|handle| lexer.output().token_text(handle))
+            .collect();
+        assert_eq!(texts, ["c", "d", "e"]);
+
+        // An edited file can be lexed again from the first line which changed
+        let edited = sources.add_file("edited.c", "a b\n# 10 \"f.c\"\nc + 1\n");
+        let restarted = Lexer::new(&sources, edited).restart_at(&full, 2).tokenize();
+        let texts: Vec<_> = (0..restarted.len())
+            .map(|i| restarted.token_text(i))
+            .collect();
+        assert_eq!(texts, ["c", "+", "1"]);
+        assert_eq!(restarted.lines().len(), 4);
+    }
+
     #[test]
     fn i64_max() {
         let source = format!("{}", i64::MAX);
-        let mut lexer = Lexer::new(&source);
+        let (sources, file) = SourceMap::anonymous(&source);
+        let mut lexer = Lexer::new(&sources, file);
 
         lexer.run_lexer();
 
@@ -392,13 +1561,14 @@ mod tests {
         assert_eq!(output.len(), 1);
 
         let token = output.get(0).unwrap();
-        assert_eq!(&source, output.token_source(token.handle).fmt(&source));
+        assert_eq!(&source, output.token_text(token.handle));
     }
 
     #[test]
     fn ident() {
         let source = "identi";
-        let mut lexer = Lexer::new(&source);
+        let (sources, file) = SourceMap::anonymous(source);
+        let mut lexer = Lexer::new(&sources, file);
 
         lexer.run_lexer();
 
@@ -406,16 +1576,20 @@ mod tests {
         assert_eq!(output.len(), 1);
 
         let token = output.get(0).unwrap();
-        assert_eq!(&source, &output.token_source(token.handle).fmt(&source));
+        assert_eq!(source, output.token_text(token.handle));
     }
 
     #[test]
     fn invalid_ident() {
         let source = "1identi";
-        let mut lexer = Lexer::new(&source);
 
-        match lexer.run_lexer() {
-            Err(LexError::InvalidNumericConstant { c: 'i' }) => (),
+        match Lexer::lex(source) {
+            Err(LexErrors { errors }) => {
+                assert_eq!(
+                    errors[0].kind,
+                    LexErrorKind::InvalidNumericConstant { c: 'i' }
+                )
+            }
             _ => panic!("Expected lexer to fail with InvalidNumericConstant 'i'"),
         }
     }
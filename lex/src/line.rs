@@ -1,6 +1,40 @@
+use std::fmt::Display;
+
 /// Line information
 #[derive(Debug, Clone, Copy)]
 pub struct Line {
     pub start: usize,
     pub end: usize,
+    /// Whether the line ends inside a block comment, in which case lexing can't restart at the
+    /// next one
+    pub ends_in_comment: bool,
+    /// Whether the line ends with a line splice, which joins it with the next one
+    pub ends_in_splice: bool,
+}
+
+/// A linemarker or `#line` directive, which says where the following lines were originally written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMarker {
+    /// Line the marker applies from, which is the one right after it
+    pub line: usize,
+    /// Original line number of `line`, starting at 0
+    pub presumed_line: usize,
+    /// Original file, `None` if no marker has named one yet
+    pub file: Option<String>,
+}
+
+/// Line and column of a position in the source, both starting at 0
+///
+/// Columns are counted in chars rather than bytes. Displaying a position shows both starting at 1,
+/// as in "3:14", which is what editors and other compilers use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
 }
@@ -0,0 +1,369 @@
+//! Validation and decoding of literals
+
+use std::ops::Range;
+
+use crate::{
+    LexErrorKind, TokenType,
+    token::{FloatSuffix, IntSuffix, Radix},
+};
+
+/// Returns the length of the preprocessing number at the start of `input`
+///
+/// A preprocessing number is a superset of every valid numeric constant: it's a digit (optionally
+/// preceded by a '.') followed by any amount of letters, digits, '_', '.' and signed exponents
+/// ("e+", "E-", "p+", ...). Lexing the whole thing before validating it means malformed constants
+/// like "1.2.3" or "1e10x" are reported as a single bad constant instead of being split into
+/// several valid tokens.
+pub(crate) fn pp_number_len(input: &str) -> usize {
+    let bytes = input.as_bytes();
+    let mut len = 0;
+
+    while let Some(&c) = bytes.get(len) {
+        match c {
+            b'e' | b'E' | b'p' | b'P' if matches!(bytes.get(len + 1), Some(b'+' | b'-')) => {
+                len += 2
+            }
+            c if c.is_ascii_alphanumeric() || c == b'_' || c == b'.' => len += 1,
+            _ => break,
+        }
+    }
+
+    len
+}
+
+/// Validates a preprocessing number, returning the kind of constant it represents
+pub(crate) fn classify_number(text: &str) -> Result<TokenType, LexErrorKind> {
+    let bytes = text.as_bytes();
+
+    let (hex, mut i) = match bytes {
+        [b'0', b'x' | b'X', ..] => (true, 2),
+        _ => (false, 0),
+    };
+    let is_digit = |c: u8| {
+        if hex {
+            c.is_ascii_hexdigit()
+        } else {
+            c.is_ascii_digit()
+        }
+    };
+    let count_digits = |i: usize| bytes[i..].iter().take_while(|c| is_digit(**c)).count();
+
+    let int_start = i;
+    let int_digits = count_digits(i);
+    i += int_digits;
+
+    let mut is_float = false;
+    let mut fraction_digits = 0;
+
+    if bytes.get(i) == Some(&b'.') {
+        is_float = true;
+        fraction_digits = count_digits(i + 1);
+        i += 1 + fraction_digits;
+    }
+
+    if int_digits + fraction_digits == 0 {
+        // Decimal constants always start with a digit, so this can only happen after a "0x"
+        return Err(LexErrorKind::MissingHexDigits);
+    }
+
+    let exponent = if hex { b"pP" } else { b"eE" };
+
+    if bytes.get(i).is_some_and(|c| exponent.contains(c)) {
+        is_float = true;
+        i += 1;
+
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+
+        let exponent_digits = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if exponent_digits == 0 {
+            return Err(LexErrorKind::MissingExponentDigits);
+        }
+        i += exponent_digits;
+    } else if hex && is_float {
+        return Err(LexErrorKind::MissingHexFloatExponent);
+    }
+
+    let suffix = &text[i..];
+
+    if suffix.contains('.') {
+        return Err(LexErrorKind::MultipleDecimalPoints);
+    }
+
+    if is_float {
+        let radix = if hex {
+            Radix::Hexadecimal
+        } else {
+            Radix::Decimal
+        };
+        let suffix = match suffix {
+            "" => FloatSuffix::None,
+            "f" | "F" => FloatSuffix::Float,
+            "l" | "L" => FloatSuffix::LongDouble,
+            suffix => return Err(invalid_suffix(suffix, "fFlL")),
+        };
+
+        return Ok(TokenType::FloatConstant { radix, suffix });
+    }
+
+    let digits = &text[int_start..int_start + int_digits];
+
+    let radix = if hex {
+        Radix::Hexadecimal
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        if let Some(c) = digits.chars().find(|c| !matches!(c, '0'..='7')) {
+            return Err(LexErrorKind::InvalidOctalDigit { c });
+        }
+        Radix::Octal
+    } else {
+        Radix::Decimal
+    };
+
+    let suffix = match suffix {
+        "" => IntSuffix::None,
+        "u" | "U" => IntSuffix::Unsigned,
+        "l" | "L" => IntSuffix::Long,
+        "ll" | "LL" => IntSuffix::LongLong,
+        "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" => IntSuffix::UnsignedLong,
+        "ull" | "uLL" | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => {
+            IntSuffix::UnsignedLongLong
+        }
+        suffix => return Err(invalid_suffix(suffix, "uUlL")),
+    };
+
+    if u64::from_str_radix(digits, radix.value()).is_err() {
+        return Err(LexErrorKind::IntegerTooLarge);
+    }
+
+    Ok(TokenType::IntConstant { radix, suffix })
+}
+
+/// Best guess of what a malformed constant was meant to be, so it can still be used as a token
+pub(crate) fn fallback_number_type(text: &str) -> TokenType {
+    let hex = text.starts_with("0x") || text.starts_with("0X");
+    let is_float = text.contains('.') || text.contains(if hex { ['p', 'P'] } else { ['e', 'E'] });
+
+    let radix = if hex {
+        Radix::Hexadecimal
+    } else {
+        Radix::Decimal
+    };
+
+    if is_float {
+        TokenType::FloatConstant {
+            radix,
+            suffix: FloatSuffix::None,
+        }
+    } else {
+        TokenType::IntConstant {
+            radix,
+            suffix: IntSuffix::None,
+        }
+    }
+}
+
+/// Picks the most helpful error for an invalid suffix: either the suffix only has valid suffix
+/// letters but in the wrong combination (e.g. "uu"), or it has a char which can't be in a suffix
+fn invalid_suffix(suffix: &str, suffix_chars: &str) -> LexErrorKind {
+    match suffix.chars().find(|c| !suffix_chars.contains(*c)) {
+        Some(c) => LexErrorKind::InvalidNumericConstant { c },
+        None => LexErrorKind::InvalidSuffix {
+            suffix: suffix.to_string(),
+        },
+    }
+}
+
+/// Returns the value of an integer constant which has already been validated by
+/// [`classify_number`]
+pub(crate) fn int_value(text: &str, radix: Radix) -> u64 {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let digits = match radix {
+        Radix::Hexadecimal => &digits[2..],
+        Radix::Octal | Radix::Decimal => digits,
+    };
+
+    u64::from_str_radix(digits, radix.value()).expect("Constant was validated while lexing")
+}
+
+/// Returns the value of a floating constant which has already been validated by
+/// [`classify_number`]
+pub(crate) fn float_value(text: &str, radix: Radix) -> f64 {
+    let text = text.trim_end_matches(['f', 'F', 'l', 'L']);
+
+    match radix {
+        Radix::Hexadecimal => {
+            let (mantissa, exponent) = text[2..]
+                .split_once(['p', 'P'])
+                .expect("Hexadecimal floating constants always have an exponent");
+            let exponent: i32 = exponent
+                .parse()
+                .expect("Constant was validated while lexing");
+
+            let mut value = 0.0;
+            let mut fraction_digits = 0;
+            let mut in_fraction = false;
+
+            for c in mantissa.chars() {
+                match c.to_digit(16) {
+                    Some(digit) => {
+                        value = value * 16.0 + digit as f64;
+                        if in_fraction {
+                            fraction_digits += 1;
+                        }
+                    }
+                    None => in_fraction = true,
+                }
+            }
+
+            value * 2f64.powi(exponent - 4 * fraction_digits)
+        }
+        Radix::Octal | Radix::Decimal => text.parse().expect("Constant was validated while lexing"),
+    }
+}
+
+/// Decodes the contents of a char or string literal (everything between the quotes), resolving
+/// escape sequences. Universal character names and any non-ASCII char are encoded as UTF-8.
+///
+/// On error, also returns the range of the offending escape sequence inside `body`.
+pub(crate) fn decode_escapes(body: &str) -> Result<Vec<u8>, (LexErrorKind, Range<usize>)> {
+    let mut bytes = Vec::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        let (_, escape) = chars
+            .next()
+            .expect("Literals can't end in a backslash since it would escape the closing quote");
+
+        let mut digits = |radix: u32, max_len: usize| {
+            let mut value: u32 = 0;
+            let mut len = 0;
+
+            while len < max_len
+                && let Some(digit) = chars.peek().and_then(|(_, c)| c.to_digit(radix))
+            {
+                value = value.saturating_mul(radix).saturating_add(digit);
+                len += 1;
+                chars.next();
+            }
+
+            (value, len)
+        };
+
+        let byte = match escape {
+            '\'' | '"' | '?' | '\\' => Ok(escape as u8),
+            'a' => Ok(0x07),
+            'b' => Ok(0x08),
+            'f' => Ok(0x0C),
+            'n' => Ok(b'\n'),
+            'r' => Ok(b'\r'),
+            't' => Ok(b'\t'),
+            'v' => Ok(0x0B),
+            '0'..='7' => {
+                // The escape itself is the first of up to three octal digits
+                let (value, len) = digits(8, 2);
+                let value = escape.to_digit(8).unwrap() * 8u32.pow(len as u32) + value;
+
+                u8::try_from(value).map_err(|_| LexErrorKind::EscapeOutOfRange)
+            }
+            'x' => match digits(16, usize::MAX) {
+                (_, 0) => Err(LexErrorKind::MissingEscapeDigits { escape }),
+                (value, _) => u8::try_from(value).map_err(|_| LexErrorKind::EscapeOutOfRange),
+            },
+            'u' | 'U' => {
+                let len = if escape == 'u' { 4 } else { 8 };
+
+                match digits(16, len) {
+                    (value, digits) if digits == len => {
+                        // Only '$', '@' and '`' can be named by a universal character below 0xA0
+                        match char::from_u32(value)
+                            .filter(|c| *c as u32 >= 0xA0 || matches!(c, '$' | '@' | '`'))
+                        {
+                            Some(c) => {
+                                let mut buf = [0; 4];
+                                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                                continue;
+                            }
+                            None => Err(LexErrorKind::InvalidUniversalChar { value }),
+                        }
+                    }
+                    _ => Err(LexErrorKind::MissingEscapeDigits { escape }),
+                }
+            }
+            c => Err(LexErrorKind::InvalidEscape { c }),
+        };
+
+        match byte {
+            Ok(byte) => bytes.push(byte),
+            Err(kind) => {
+                let end = chars.peek().map_or(body.len(), |(i, _)| *i);
+                return Err((kind, start..end));
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Returns the value of a decoded char literal
+///
+/// Plain `char` is signed on x86-64, so a single byte gets sign extended ('\377' is -1), while
+/// multi-char literals are packed into an int in big-endian order like gcc and clang do.
+pub(crate) fn char_value(bytes: &[u8]) -> i32 {
+    match bytes {
+        [byte] => *byte as i8 as i32,
+        bytes => bytes
+            .iter()
+            .fold(0i32, |value, byte| (value << 8) | *byte as i32),
+    }
+}
+
+/// Parses the contents of a linemarker or `#line` directive after the '#', which are either
+/// `line 42 "file.c"` or `42 "file.c" flags...`, returning the line number and file name
+pub(crate) fn parse_line_marker(text: &str) -> Option<(usize, Option<String>)> {
+    let text = text.trim_start();
+    let text = match text.strip_prefix("line") {
+        Some(rest) if rest.starts_with([' ', '\t']) => rest.trim_start(),
+        Some(_) => return None,
+        None => text,
+    };
+
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let line = text[..digits].parse().ok()?;
+    let rest = text[digits..].trim_start();
+
+    let Some(file) = rest.strip_prefix('"') else {
+        return rest.is_empty().then_some((line, None));
+    };
+
+    let mut chars = file.char_indices();
+    let end = loop {
+        match chars.next()? {
+            (_, '\\') => {
+                chars.next()?;
+            }
+            (i, '"') => break i,
+            _ => (),
+        }
+    };
+
+    // Only flags can come after the file name
+    if !file[end + 1..]
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace())
+    {
+        return None;
+    }
+
+    let file = decode_escapes(&file[..end]).ok()?;
+
+    Some((line, Some(String::from_utf8_lossy(&file).into_owned())))
+}
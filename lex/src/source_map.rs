@@ -0,0 +1,198 @@
+use std::{
+    fmt::{self, Debug},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{line::Position, token::Span, translation::Translation};
+
+/// Identifies a file in a [`SourceMap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+/// A file loaded into a [`SourceMap`]
+pub struct SourceFile {
+    path: PathBuf,
+    source: Arc<str>,
+    /// Byte offset where each line starts, the first one always being 0
+    line_starts: Vec<usize>,
+    /// The source after replacing trigraphs and joining spliced lines, if that changes anything
+    translation: Option<Arc<Translation>>,
+}
+
+impl SourceFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The source after translation phases 1 and 2, which is what gets lexed
+    pub(crate) fn translated_source(&self) -> &str {
+        match &self.translation {
+            Some(translation) => &translation.text,
+            None => &self.source,
+        }
+    }
+
+    pub(crate) fn translation(&self) -> Option<&Translation> {
+        self.translation.as_deref()
+    }
+}
+
+impl Debug for SourceFile {
+    // Line starts and the translation are left out since they're derived from the source
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceFile")
+            .field("path", &self.path)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// Every source file used in a compilation, each one identified by a [`FileId`]
+///
+/// Files are never removed or modified once added, so ids stay valid and tokens from any number of
+/// files can refer back to their text. Cloning is cheap since the sources themselves are shared.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<Arc<SourceFile>>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Map with `source` as its only file, for when there's no actual file to read from
+    pub fn anonymous(source: &str) -> (SourceMap, FileId) {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file("<input>", source);
+
+        (sources, file)
+    }
+
+    /// Adds a file which was read from `path`
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, source: impl Into<Arc<str>>) -> FileId {
+        let source: Arc<str> = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let translation = Translation::new(&source).map(Arc::new);
+
+        self.push(SourceFile {
+            path: path.into(),
+            source,
+            line_starts,
+            translation,
+        })
+    }
+
+    /// Reads the file at `path` and adds it
+    pub fn load(&mut self, path: &Path) -> io::Result<FileId> {
+        let source = std::fs::read_to_string(path)?;
+
+        Ok(self.add_file(path, source))
+    }
+
+    /// Adds a file with the same contents as `file` but another path, which is what `#line`
+    /// directives do
+    pub fn rename(&mut self, file: FileId, path: impl Into<PathBuf>) -> FileId {
+        let file = self.file(file);
+        let renamed = SourceFile {
+            path: path.into(),
+            source: file.source.clone(),
+            line_starts: file.line_starts.clone(),
+            translation: file.translation.clone(),
+        };
+
+        self.push(renamed)
+    }
+
+    fn push(&mut self, file: SourceFile) -> FileId {
+        let id = FileId(u32::try_from(self.files.len()).expect("Too many source files"));
+        self.files.push(Arc::new(file));
+        id
+    }
+
+    pub fn file(&self, file: FileId) -> &SourceFile {
+        &self.files[file.0 as usize]
+    }
+
+    pub fn path(&self, file: FileId) -> &Path {
+        self.file(file).path()
+    }
+
+    pub fn source(&self, file: FileId) -> &str {
+        self.file(file).source()
+    }
+
+    /// Text of `file` covered by `span`
+    pub fn text(&self, file: FileId, span: Span) -> &str {
+        &self.source(file)[span.start..span.end]
+    }
+
+    /// Text of `file` covered by `span` with trigraphs replaced and line splices removed, which is
+    /// how tokens are spelled
+    pub fn spelling(&self, file: FileId, span: Span) -> &str {
+        let file = self.file(file);
+
+        match &file.translation {
+            Some(translation) => {
+                let start = translation.translated_offset(span.start);
+                let end = translation.translated_offset(span.end);
+                &translation.text[start..end]
+            }
+            None => &file.source[span.start..span.end],
+        }
+    }
+
+    /// Line and column of a byte offset into `file`
+    pub fn position(&self, file: FileId, offset: usize) -> Position {
+        let file = self.file(file);
+
+        // Lines are sorted by their starting offset, so we can binary search for the last line
+        // which starts at or before `offset`
+        let line = file
+            .line_starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1);
+        let column = file.source[file.line_starts[line]..offset].chars().count();
+
+        Position { line, column }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{line::Position, source_map::SourceMap, token::Span};
+
+    #[test]
+    fn files() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file("main.c", "int x;\nint y;");
+        let header = sources.add_file("header.h", "long z;\n");
+        let renamed = sources.rename(main, "other.c");
+
+        assert_eq!(sources.path(header), Path::new("header.h"));
+        assert_eq!(sources.text(main, Span { start: 11, end: 12 }), "y");
+        assert_eq!(sources.text(renamed, Span { start: 11, end: 12 }), "y");
+        assert_eq!(sources.path(renamed), Path::new("other.c"));
+        assert_eq!(sources.position(main, 11), Position { line: 1, column: 4 });
+        assert_eq!(sources.position(header, 8), Position { line: 1, column: 0 });
+
+        let spliced = sources.add_file("spliced.c", "in\\\nt x??(1];");
+        assert_eq!(sources.text(spliced, Span { start: 0, end: 5 }), "in\\\nt");
+        assert_eq!(sources.spelling(spliced, Span { start: 0, end: 5 }), "int");
+        assert_eq!(sources.spelling(spliced, Span { start: 7, end: 10 }), "[");
+        assert_eq!(
+            sources.position(spliced, 6),
+            Position { line: 1, column: 2 }
+        );
+    }
+}
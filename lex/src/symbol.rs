@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    ops::Index,
+    sync::Arc,
+};
+
+/// An interned name, which can be compared and hashed as cheaply as an integer
+///
+/// Symbols are only meaningful for the [`SymbolTable`] which created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// Every distinct name used in a compilation, each one identified by a [`Symbol`]
+///
+/// The lexer interns identifiers into it and later stages carry it along, so names they generate,
+/// like temporaries, live in the same table.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    names: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Symbol for `name`, which is only added if it wasn't interned before
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+
+        let symbol = Symbol(u32::try_from(self.names.len()).expect("Too many symbols"));
+        let name: Arc<str> = name.into();
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// Symbol for `name` if it was already interned
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl Index<Symbol> for SymbolTable {
+    type Output = str;
+
+    fn index(&self, symbol: Symbol) -> &str {
+        self.name(symbol)
+    }
+}
+
+impl Debug for SymbolTable {
+    // The map is left out since it has the same names, in no particular order
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SymbolTable")
+            .field("names", &self.names)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol::SymbolTable;
+
+    #[test]
+    fn intern() {
+        let mut symbols = SymbolTable::new();
+        let main = symbols.intern("main");
+        let x = symbols.intern("x");
+
+        assert_ne!(main, x);
+        assert_eq!(symbols.intern("main"), main);
+        assert_eq!(symbols.get("x"), Some(x));
+        assert_eq!(symbols.get("y"), None);
+        assert_eq!(&symbols[main], "main");
+        assert_eq!(symbols.len(), 2);
+    }
+}
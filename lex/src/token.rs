@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::source_map::FileId;
+
 /// Small token which only has the token type, error flag and a handle for related information such
 /// as position in the source text, line, etc.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -9,56 +11,427 @@ pub struct Token {
     pub handle: usize,
 }
 
+/// Where a token was lexed from
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TokenSource {
+    pub file: FileId,
+    pub span: Span,
+    /// Line of `file` the token is in
+    pub line: usize,
+}
+
+/// Byte range [start, end) of the source text, used by every stage to point errors at the code
+/// that caused them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
     pub start: usize,
     pub end: usize,
-    pub line: usize,
 }
 
-impl<'src> TokenSource {
-    pub fn fmt(&self, source: &'src str) -> &'src str {
-        &source[self.start..self.end]
+impl Span {
+    /// Smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
     Ident,
-    Keyword,
-    Constant,
+    Keyword(Keyword),
+    IntConstant {
+        radix: Radix,
+        suffix: IntSuffix,
+    },
+    FloatConstant {
+        radix: Radix,
+        suffix: FloatSuffix,
+    },
+    CharLiteral,
+    StringLiteral,
+    /// A char which doesn't start any valid token
+    Unknown,
     OpenParen,
     CloseParen,
     OpenBrace,
     CloseBrace,
+    OpenBracket,  // '['
+    CloseBracket, // ']'
     Semicolon,
-    Colon,       // ':'
-    FrontSlash,  // '/'
-    Hyphen,      // '-'
-    Asterisk,    // '*'
-    Quote,       // "'"
-    DoubleQuote, // '"'
-    Comma,       // ','
+    Colon,           // ':'
+    Comma,           // ','
+    Dot,             // '.'
+    Ellipsis,        // '...'
+    Arrow,           // '->'
+    Question,        // '?'
+    Plus,            // '+'
+    Hyphen,          // '-'
+    Asterisk,        // '*'
+    FrontSlash,      // '/'
+    Percent,         // '%'
+    Increment,       // '++'
+    Decrement,       // '--'
+    Ampersand,       // '&'
+    Pipe,            // '|'
+    Caret,           // '^'
+    Tilde,           // '~'
+    ShiftLeft,       // '<<'
+    ShiftRight,      // '>>'
+    Bang,            // '!'
+    LogicalAnd,      // '&&'
+    LogicalOr,       // '||'
+    Equal,           // '='
+    EqualEqual,      // '=='
+    NotEqual,        // '!='
+    Less,            // '<'
+    Greater,         // '>'
+    LessEqual,       // '<='
+    GreaterEqual,    // '>='
+    PlusEqual,       // '+='
+    HyphenEqual,     // '-='
+    AsteriskEqual,   // '*='
+    FrontSlashEqual, // '/='
+    PercentEqual,    // '%='
+    AmpersandEqual,  // '&='
+    PipeEqual,       // '|='
+    CaretEqual,      // '^='
+    ShiftLeftEqual,  // '<<='
+    ShiftRightEqual, // '>>='
+    Hash,            // '#'
+    HashHash,        // '##'
+}
+
+/// Base a numeric constant is written in
+///
+/// A lone "0" is considered decimal, even though the standard defines it as an octal constant,
+/// since that's what anyone reading it would expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    pub fn value(&self) -> u32 {
+        match self {
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+}
+
+/// Suffix of an integer constant, the order and case of the letters is not kept ("lu" and "UL"
+/// are both `UnsignedLong`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntSuffix {
+    None,
+    Unsigned,         // 'u'
+    Long,             // 'l'
+    UnsignedLong,     // 'ul'
+    LongLong,         // 'll'
+    UnsignedLongLong, // 'ull'
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatSuffix {
+    None,
+    Float,      // 'f'
+    LongDouble, // 'l'
+}
+
+/// Every punctuator and its spelling, sorted so that longer punctuators come first. Picking the
+/// first entry which matches the input is then enough to get the longest possible token (maximal
+/// munch), e.g. "<<=" is tried before "<<", which is tried before "<".
+///
+/// Digraphs are spelled differently but are otherwise the same as the punctuators they stand for.
+pub(crate) const PUNCTUATORS: [(&str, TokenType); 54] = [
+    ("%:%:", TokenType::HashHash),
+    ("...", TokenType::Ellipsis),
+    ("<<=", TokenType::ShiftLeftEqual),
+    (">>=", TokenType::ShiftRightEqual),
+    ("->", TokenType::Arrow),
+    ("++", TokenType::Increment),
+    ("--", TokenType::Decrement),
+    ("<<", TokenType::ShiftLeft),
+    (">>", TokenType::ShiftRight),
+    ("&&", TokenType::LogicalAnd),
+    ("||", TokenType::LogicalOr),
+    ("==", TokenType::EqualEqual),
+    ("!=", TokenType::NotEqual),
+    ("<=", TokenType::LessEqual),
+    (">=", TokenType::GreaterEqual),
+    ("+=", TokenType::PlusEqual),
+    ("-=", TokenType::HyphenEqual),
+    ("*=", TokenType::AsteriskEqual),
+    ("/=", TokenType::FrontSlashEqual),
+    ("%=", TokenType::PercentEqual),
+    ("&=", TokenType::AmpersandEqual),
+    ("|=", TokenType::PipeEqual),
+    ("^=", TokenType::CaretEqual),
+    ("##", TokenType::HashHash),
+    ("<:", TokenType::OpenBracket),
+    (":>", TokenType::CloseBracket),
+    ("<%", TokenType::OpenBrace),
+    ("%>", TokenType::CloseBrace),
+    ("%:", TokenType::Hash),
+    ("(", TokenType::OpenParen),
+    (")", TokenType::CloseParen),
+    ("{", TokenType::OpenBrace),
+    ("}", TokenType::CloseBrace),
+    ("[", TokenType::OpenBracket),
+    ("]", TokenType::CloseBracket),
+    (";", TokenType::Semicolon),
+    (":", TokenType::Colon),
+    (",", TokenType::Comma),
+    (".", TokenType::Dot),
+    ("?", TokenType::Question),
+    ("+", TokenType::Plus),
+    ("-", TokenType::Hyphen),
+    ("*", TokenType::Asterisk),
+    ("/", TokenType::FrontSlash),
+    ("%", TokenType::Percent),
+    ("&", TokenType::Ampersand),
+    ("|", TokenType::Pipe),
+    ("^", TokenType::Caret),
+    ("~", TokenType::Tilde),
+    ("!", TokenType::Bang),
+    ("=", TokenType::Equal),
+    ("<", TokenType::Less),
+    (">", TokenType::Greater),
+    ("#", TokenType::Hash),
+];
+
+/// Most punctuators which start with the same char, "<<=", "<<", "<=", "<:", "<%" and "<"
+const MAX_PUNCTUATORS_PER_BYTE: usize = 6;
+
+/// Indices into `PUNCTUATORS` of the punctuators which start with each ASCII byte, in the same
+/// order. Indices start at 1, so that 0 marks the end of the list.
+static PUNCTUATORS_BY_BYTE: [[u8; MAX_PUNCTUATORS_PER_BYTE]; 128] = punctuators_by_byte();
+
+const fn punctuators_by_byte() -> [[u8; MAX_PUNCTUATORS_PER_BYTE]; 128] {
+    let mut table = [[0; MAX_PUNCTUATORS_PER_BYTE]; 128];
+    let mut i = 0;
+
+    while i < PUNCTUATORS.len() {
+        let first = PUNCTUATORS[i].0.as_bytes()[0] as usize;
+        let mut slot = 0;
+
+        while table[first][slot] != 0 {
+            slot += 1;
+        }
+        table[first][slot] = i as u8 + 1;
+
+        i += 1;
+    }
+
+    table
+}
+
+/// The longest punctuator `input` starts with
+pub(crate) fn punctuator(input: &str) -> Option<(&'static str, TokenType)> {
+    let first = *input.as_bytes().first()?;
+    let candidates = PUNCTUATORS_BY_BYTE.get(first as usize)?;
+
+    candidates
+        .iter()
+        .take_while(|&&i| i != 0)
+        .map(|&i| PUNCTUATORS[i as usize - 1])
+        .find(|(punctuator, _)| input.starts_with(punctuator))
 }
 
+/// All keywords defined by the C11 standard
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Keyword {
-    Return,
+    Auto,
+    Break,
+    Case,
+    Char,
+    Const,
+    Continue,
+    Default,
+    Do,
+    Double,
+    Else,
+    Enum,
+    Extern,
+    Float,
+    For,
+    Goto,
+    If,
+    Inline,
     Int,
+    Long,
+    Register,
+    Restrict,
+    Return,
+    Short,
+    Signed,
+    Sizeof,
+    Static,
+    Struct,
+    Switch,
+    Typedef,
+    Union,
+    Unsigned,
     Void,
+    Volatile,
+    While,
+    Alignas,
+    Alignof,
+    Atomic,
+    Bool,
+    Complex,
+    Generic,
+    Imaginary,
+    Noreturn,
+    StaticAssert,
+    ThreadLocal,
+}
+
+impl Keyword {
+    pub const ALL: [Keyword; 44] = [
+        Keyword::Auto,
+        Keyword::Break,
+        Keyword::Case,
+        Keyword::Char,
+        Keyword::Const,
+        Keyword::Continue,
+        Keyword::Default,
+        Keyword::Do,
+        Keyword::Double,
+        Keyword::Else,
+        Keyword::Enum,
+        Keyword::Extern,
+        Keyword::Float,
+        Keyword::For,
+        Keyword::Goto,
+        Keyword::If,
+        Keyword::Inline,
+        Keyword::Int,
+        Keyword::Long,
+        Keyword::Register,
+        Keyword::Restrict,
+        Keyword::Return,
+        Keyword::Short,
+        Keyword::Signed,
+        Keyword::Sizeof,
+        Keyword::Static,
+        Keyword::Struct,
+        Keyword::Switch,
+        Keyword::Typedef,
+        Keyword::Union,
+        Keyword::Unsigned,
+        Keyword::Void,
+        Keyword::Volatile,
+        Keyword::While,
+        Keyword::Alignas,
+        Keyword::Alignof,
+        Keyword::Atomic,
+        Keyword::Bool,
+        Keyword::Complex,
+        Keyword::Generic,
+        Keyword::Imaginary,
+        Keyword::Noreturn,
+        Keyword::StaticAssert,
+        Keyword::ThreadLocal,
+    ];
+
+    /// Returns the keyword spelled exactly as `ident`, if there is one
+    pub fn from_ident(ident: &str) -> Option<Keyword> {
+        let candidates = KEYWORDS_BY_LEN.get(ident.len())?;
+
+        candidates
+            .iter()
+            .take_while(|&&i| i != 0)
+            .map(|&i| Keyword::ALL[i as usize - 1])
+            .find(|keyword| keyword.as_str() == ident)
+    }
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Auto => "auto",
+            Keyword::Break => "break",
+            Keyword::Case => "case",
+            Keyword::Char => "char",
+            Keyword::Const => "const",
+            Keyword::Continue => "continue",
+            Keyword::Default => "default",
+            Keyword::Do => "do",
+            Keyword::Double => "double",
+            Keyword::Else => "else",
+            Keyword::Enum => "enum",
+            Keyword::Extern => "extern",
+            Keyword::Float => "float",
+            Keyword::For => "for",
+            Keyword::Goto => "goto",
+            Keyword::If => "if",
+            Keyword::Inline => "inline",
+            Keyword::Int => "int",
+            Keyword::Long => "long",
+            Keyword::Register => "register",
+            Keyword::Restrict => "restrict",
+            Keyword::Return => "return",
+            Keyword::Short => "short",
+            Keyword::Signed => "signed",
+            Keyword::Sizeof => "sizeof",
+            Keyword::Static => "static",
+            Keyword::Struct => "struct",
+            Keyword::Switch => "switch",
+            Keyword::Typedef => "typedef",
+            Keyword::Union => "union",
+            Keyword::Unsigned => "unsigned",
+            Keyword::Void => "void",
+            Keyword::Volatile => "volatile",
+            Keyword::While => "while",
+            Keyword::Alignas => "_Alignas",
+            Keyword::Alignof => "_Alignof",
+            Keyword::Atomic => "_Atomic",
+            Keyword::Bool => "_Bool",
+            Keyword::Complex => "_Complex",
+            Keyword::Generic => "_Generic",
+            Keyword::Imaginary => "_Imaginary",
+            Keyword::Noreturn => "_Noreturn",
+            Keyword::StaticAssert => "_Static_assert",
+            Keyword::ThreadLocal => "_Thread_local",
+        }
+    }
+}
+
+/// Most keywords with the same length, which is 6 and 8
+const MAX_KEYWORDS_PER_LEN: usize = 9;
+
+/// Indices into `Keyword::ALL` of the keywords of each length, starting at 1 so that 0 marks the
+/// end of the list. Checking only keywords of the right length is much faster than checking all of
+/// them, and identifiers are very common.
+static KEYWORDS_BY_LEN: [[u8; MAX_KEYWORDS_PER_LEN]; 15] = keywords_by_len();
+
+const fn keywords_by_len() -> [[u8; MAX_KEYWORDS_PER_LEN]; 15] {
+    let mut table = [[0; MAX_KEYWORDS_PER_LEN]; 15];
+    let mut i = 0;
+
+    while i < Keyword::ALL.len() {
+        let len = Keyword::ALL[i].as_str().len();
+        let mut slot = 0;
+
+        while table[len][slot] != 0 {
+            slot += 1;
+        }
+        table[len][slot] = i as u8 + 1;
+
+        i += 1;
+    }
+
+    table
 }
 
 impl Display for Keyword {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Keyword::Void => "void",
-                Keyword::Int => "int",
-                Keyword::Return => "return",
-            }
-        )
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -66,20 +439,60 @@ impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenType::Ident => write!(f, "Ident"),
-            TokenType::Keyword => todo!(),
-            TokenType::Constant => write!(f, "Constant"),
+            TokenType::Keyword(keyword) => write!(f, "Keyword({keyword})"),
+            TokenType::IntConstant { .. } => write!(f, "IntConstant"),
+            TokenType::FloatConstant { .. } => write!(f, "FloatConstant"),
+            TokenType::CharLiteral => write!(f, "CharLiteral"),
+            TokenType::StringLiteral => write!(f, "StringLiteral"),
+            TokenType::Unknown => write!(f, "Unknown"),
             TokenType::OpenParen => write!(f, "OpenParen"),
             TokenType::CloseParen => write!(f, "CloseParen"),
             TokenType::OpenBrace => write!(f, "OpenBrace"),
             TokenType::CloseBrace => write!(f, "CloseBrace"),
+            TokenType::OpenBracket => write!(f, "OpenBracket"),
+            TokenType::CloseBracket => write!(f, "CloseBracket"),
             TokenType::Semicolon => write!(f, "Semicolon"),
             TokenType::Colon => write!(f, "Colon"),
-            TokenType::FrontSlash => write!(f, "FrontsSlash"),
+            TokenType::Comma => write!(f, "Comma"),
+            TokenType::Dot => write!(f, "Dot"),
+            TokenType::Ellipsis => write!(f, "Ellipsis"),
+            TokenType::Arrow => write!(f, "Arrow"),
+            TokenType::Question => write!(f, "Question"),
+            TokenType::Plus => write!(f, "Plus"),
             TokenType::Hyphen => write!(f, "Hyphen"),
             TokenType::Asterisk => write!(f, "Asterisk"),
-            TokenType::Quote => write!(f, "Quote"),
-            TokenType::DoubleQuote => write!(f, "DoubleQuote"),
-            TokenType::Comma => write!(f, "Comma"),
+            TokenType::FrontSlash => write!(f, "FrontSlash"),
+            TokenType::Percent => write!(f, "Percent"),
+            TokenType::Increment => write!(f, "Increment"),
+            TokenType::Decrement => write!(f, "Decrement"),
+            TokenType::Ampersand => write!(f, "Ampersand"),
+            TokenType::Pipe => write!(f, "Pipe"),
+            TokenType::Caret => write!(f, "Caret"),
+            TokenType::Tilde => write!(f, "Tilde"),
+            TokenType::ShiftLeft => write!(f, "ShiftLeft"),
+            TokenType::ShiftRight => write!(f, "ShiftRight"),
+            TokenType::Bang => write!(f, "Bang"),
+            TokenType::LogicalAnd => write!(f, "LogicalAnd"),
+            TokenType::LogicalOr => write!(f, "LogicalOr"),
+            TokenType::Equal => write!(f, "Equal"),
+            TokenType::EqualEqual => write!(f, "EqualEqual"),
+            TokenType::NotEqual => write!(f, "NotEqual"),
+            TokenType::Less => write!(f, "Less"),
+            TokenType::Greater => write!(f, "Greater"),
+            TokenType::LessEqual => write!(f, "LessEqual"),
+            TokenType::GreaterEqual => write!(f, "GreaterEqual"),
+            TokenType::PlusEqual => write!(f, "PlusEqual"),
+            TokenType::HyphenEqual => write!(f, "HyphenEqual"),
+            TokenType::AsteriskEqual => write!(f, "AsteriskEqual"),
+            TokenType::FrontSlashEqual => write!(f, "FrontSlashEqual"),
+            TokenType::PercentEqual => write!(f, "PercentEqual"),
+            TokenType::AmpersandEqual => write!(f, "AmpersandEqual"),
+            TokenType::PipeEqual => write!(f, "PipeEqual"),
+            TokenType::CaretEqual => write!(f, "CaretEqual"),
+            TokenType::ShiftLeftEqual => write!(f, "ShiftLeftEqual"),
+            TokenType::ShiftRightEqual => write!(f, "ShiftRightEqual"),
+            TokenType::Hash => write!(f, "Hash"),
+            TokenType::HashHash => write!(f, "HashHash"),
         }
     }
 }
@@ -0,0 +1,175 @@
+//! Translation phases 1 and 2, which replace trigraphs and join lines ending in a backslash
+//!
+//! Both are rare in practice, so files are only translated when they need it. The lexer works on
+//! the translated text, but every offset it records is mapped back to the original text so spans
+//! and lines still refer to what's actually in the file.
+
+use memchr::memchr2;
+
+/// Text of a file after phases 1 and 2, along with where it differs from the original
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Translation {
+    pub(crate) text: String,
+    /// Every trigraph and line splice, sorted by offset
+    pub(crate) edits: Vec<Edit>,
+}
+
+/// A trigraph or line splice, described by the offsets right after it in both texts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Edit {
+    pub(crate) translated: usize,
+    pub(crate) original: usize,
+    /// Whether it's a line splice, which removes a newline from the text
+    pub(crate) splice: bool,
+}
+
+impl Translation {
+    /// Translates `source`, `None` if it has neither trigraphs nor line splices
+    pub(crate) fn new(source: &str) -> Option<Translation> {
+        let bytes = source.as_bytes();
+        let mut text = String::new();
+        let mut edits = Vec::new();
+        // Everything before this has already been copied or translated into `text`
+        let mut copied = 0;
+        let mut i = 0;
+
+        while let Some(found) = memchr2(b'\\', b'?', &bytes[i..]) {
+            let start = i + found;
+            let (c, len) = match bytes[start] {
+                b'?' => match bytes.get(start + 2).and_then(|&byte| trigraph(byte)) {
+                    Some(c) if bytes[start + 1] == b'?' => (c, 3),
+                    _ => {
+                        i = start + 1;
+                        continue;
+                    }
+                },
+                _ => ('\\', 1),
+            };
+
+            let newline = match &bytes[start + len..] {
+                [b'\n', ..] if c == '\\' => 1,
+                [b'\r', b'\n', ..] if c == '\\' => 2,
+                _ => 0,
+            };
+
+            i = start + len + newline;
+
+            if c == '\\' && newline == 0 && len == 1 {
+                // Just a backslash
+                continue;
+            }
+
+            text.push_str(&source[copied..start]);
+            if newline == 0 {
+                text.push(c);
+            }
+            copied = i;
+
+            edits.push(Edit {
+                translated: text.len(),
+                original: i,
+                splice: newline != 0,
+            });
+        }
+
+        if edits.is_empty() {
+            return None;
+        }
+
+        text.push_str(&source[copied..]);
+
+        Some(Translation { text, edits })
+    }
+
+    /// Offset into the original text of an offset into the translated one
+    ///
+    /// Offsets right after an edit are mapped to right after it in the original text too, so
+    /// spans ending there include the whole trigraph or line splice. The only exception is the
+    /// start of the text, so the first span includes any line splices the file starts with.
+    pub(crate) fn original_offset(&self, translated: usize) -> usize {
+        match self
+            .edits
+            .partition_point(|edit| edit.translated <= translated)
+        {
+            _ if translated == 0 => 0,
+            0 => translated,
+            i => {
+                let edit = self.edits[i - 1];
+                edit.original + (translated - edit.translated)
+            }
+        }
+    }
+
+    /// Offset into the translated text of an offset into the original one, offsets inside a
+    /// trigraph or line splice are mapped to right after it
+    pub(crate) fn translated_offset(&self, original: usize) -> usize {
+        let i = self.edits.partition_point(|edit| edit.original <= original);
+        let translated = match i {
+            0 => original,
+            i => {
+                let edit = self.edits[i - 1];
+                edit.translated + (original - edit.original)
+            }
+        };
+
+        match self.edits.get(i) {
+            Some(next) => translated.min(next.translated),
+            None => translated,
+        }
+    }
+}
+
+/// Char which the trigraph `??<byte>` stands for
+fn trigraph(byte: u8) -> Option<char> {
+    let c = match byte {
+        b'=' => '#',
+        b'(' => '[',
+        b'/' => '\\',
+        b')' => ']',
+        b'\'' => '^',
+        b'<' => '{',
+        b'!' => '|',
+        b'>' => '}',
+        b'-' => '~',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::translation::Translation;
+
+    #[test]
+    fn nothing_to_translate() {
+        assert_eq!(
+            Translation::new("int main(void) { return '\\n'; } ?? ?"),
+            None
+        );
+    }
+
+    #[test]
+    fn translate() {
+        let source = "??=define X ??/\n  1 \\\r\nx\\\n\\\n?\\ ??";
+        let translation = Translation::new(source).unwrap();
+
+        assert_eq!(translation.text, "#define X   1 x?\\ ??");
+        assert_eq!(translation.edits.len(), 5);
+
+        // '1', after a splice made of a trigraph
+        assert_eq!(translation.original_offset(12), 18);
+        assert_eq!(translation.translated_offset(18), 12);
+        // Inside that splice
+        assert_eq!(translation.translated_offset(14), 10);
+        // 'x', after a CRLF splice
+        assert_eq!(translation.original_offset(14), 23);
+        assert_eq!(translation.translated_offset(23), 14);
+        // '?' after two splices in a row
+        assert_eq!(translation.original_offset(15), 28);
+        assert_eq!(
+            translation.original_offset(translation.text.len()),
+            source.len()
+        );
+    }
+}
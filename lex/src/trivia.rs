@@ -0,0 +1,37 @@
+use std::ops::Range;
+
+use crate::token::Span;
+
+/// Source text between tokens which doesn't affect the program, only kept when lexing with
+/// [`Lexer::keep_trivia`](crate::Lexer::keep_trivia)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of whitespace other than '\n'
+    Whitespace,
+    Newline,
+    /// A `//` comment, without the '\n' which ends it
+    LineComment,
+    /// A `/* */` comment, which may be unterminated
+    BlockComment,
+    /// A linemarker or `#line` directive interpreted by the lexer, without the '\n' which ends it
+    LineMarker,
+    /// Everything after a NUL char, which ends the input
+    Ignored,
+}
+
+/// Which trivia a token owns, as indices into the output's trivia
+///
+/// A token's trailing trivia is everything after it up to the end of its line, and its leading
+/// trivia is everything else since the previous token. This is how comments at the end of a line
+/// end up with the code they describe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TokenTrivia {
+    pub(crate) leading: Range<usize>,
+    pub(crate) trailing: Range<usize>,
+}
@@ -0,0 +1,94 @@
+use std::{error::Error, fmt::Display};
+
+use lex::{TokenType, source_map::FileId, token::Span};
+
+/// A syntax error and the source text it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub file: FileId,
+    /// Span of the offending token, or an empty span at the end of the input if it ended too early
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedToken {
+        expected: Expected,
+        found: TokenType,
+    },
+    UnexpectedEof {
+        expected: Expected,
+    },
+    ConstantTooLarge {
+        value: u64,
+    },
+}
+
+/// What the parser was looking for when it found something else
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Token(TokenType),
+    Constant,
+    Expression,
+    Statement,
+    EndOfInput,
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expected::Token(ttype) => write!(f, "[{ttype}]"),
+            Expected::Constant => write!(f, "[IntConstant]"),
+            Expected::Expression => write!(f, "expression"),
+            Expected::Statement => write!(f, "statement"),
+            Expected::EndOfInput => write!(f, "end of input"),
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedToken { expected, found } => {
+                write!(f, "Expected {expected}, got [{found}]")
+            }
+            ParseErrorKind::UnexpectedEof { expected } => {
+                write!(f, "Expected {expected}, got end of input")
+            }
+            ParseErrorKind::ConstantTooLarge { value } => {
+                write!(f, "Integer constant {value} doesn't fit in an i64")
+            }
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Every syntax error found while parsing an input, in the order they appear in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrors {
+    pub errors: Vec<ParseError>,
+}
+
+impl Display for ParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseErrors {}
+
+pub type ParseResult<T> = Result<T, ParseError>;
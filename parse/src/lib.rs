@@ -1,142 +1,688 @@
 use ast::{
-    Constant, ConstantId, Expr, ExprId, FnDef, FnDefId, Ident, IdentId, Program, ProgramId, Stmt,
-    StmtId, Tree,
+    BinaryOp, Block, BlockId, BlockItem, Constant, ConstantId, Decl, DeclId, Expr, ExprId, Fixity,
+    FnDef, FnDefId, Ident, IdentId, IncrementOp, Program, ProgramId, Stmt, StmtId, Tree, UnaryOp,
 };
-use lex::{Token, TokenType, TokenizedOutput, token::Keyword};
-use tracing::{Level, error, span};
+use lex::{
+    Token, TokenType, TokenizedOutput,
+    token::{Keyword, Span},
+};
+use tracing::{Level, span};
+
+pub mod error;
+pub use error::*;
 
-pub struct Parser<'src> {
-    pub nodes: Tree<'src>,
+pub struct Parser {
+    pub nodes: Tree,
     cur_token: usize,
+    errors: Vec<ParseError>,
 }
 
-impl<'src> Parser<'src> {
-    pub fn from_tokens(tokens: TokenizedOutput<'src>) -> Parser<'src> {
+impl Parser {
+    pub fn from_tokens(tokens: TokenizedOutput) -> Parser {
         // Size optimization where we "guess" we'll have around the same number of ast nodes and tokens
         // This is not exactly correct, but it's good enough
         let len = tokens.len();
         Parser {
             nodes: Tree::with_capacity(tokens, len),
             cur_token: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn nodes(&'src self) -> &'src Tree<'src> {
+    pub fn nodes(&self) -> &Tree {
         &self.nodes
     }
 
-    pub fn tokens(&'src self) -> &'src TokenizedOutput<'src> {
+    pub fn tokens(&self) -> &TokenizedOutput {
         &self.nodes.tokens
     }
 
-    fn expect(&mut self, ttype: TokenType) -> Token {
-        let token = self.tokens().get(self.cur_token).unwrap();
+    /// Errors found while parsing, in the order they appear in the source
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Next token, failing if the input ended
+    fn peek(&self, expected: Expected) -> ParseResult<Token> {
+        self.tokens()
+            .get(self.cur_token)
+            .ok_or_else(|| self.error_at_end(ParseErrorKind::UnexpectedEof { expected }))
+    }
+
+    fn expect(&mut self, ttype: TokenType) -> ParseResult<Token> {
+        let token = self.peek(Expected::Token(ttype))?;
 
         if ttype != token.ttype {
-            panic!("Expected [{}], got [{}]", ttype, token.ttype);
+            return Err(self.unexpected(Expected::Token(ttype), token));
         }
         self.cur_token += 1;
 
-        token
+        Ok(token)
     }
 
-    fn expect_ident(&mut self) -> IdentId {
-        let token = self.expect(TokenType::Ident);
+    fn expect_ident(&mut self) -> ParseResult<IdentId> {
+        let token = self.expect(TokenType::Ident)?;
+
+        let name = self.tokens().symbol(token.handle);
+        let ident = Ident { name, token };
 
-        let ident = Ident { token };
+        Ok(self.nodes.push(ident))
+    }
 
-        self.nodes.push(ident)
+    fn expect_keyword(&mut self, keyword: Keyword) -> ParseResult<Token> {
+        self.expect(TokenType::Keyword(keyword))
     }
 
-    fn expect_keyword(&mut self, keyword: Keyword) -> Token {
-        let token = self.expect(TokenType::Ident);
+    /// Error at `token`
+    fn error(&self, kind: ParseErrorKind, token: Token) -> ParseError {
+        let source = self.tokens().token_source(token.handle);
+
+        ParseError {
+            kind,
+            file: source.file,
+            span: source.span,
+        }
+    }
 
-        let source = self.tokens().token_text(token.handle);
+    /// Error at the end of the input
+    fn error_at_end(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            file: self.tokens().file(),
+            span: self.end_span(),
+        }
+    }
 
-        // TODO propagate error
-        assert_eq!(source, format!("{}", keyword));
+    /// Empty span at the end of the input
+    fn end_span(&self) -> Span {
+        let end = self.tokens().sources().source(self.tokens().file()).len();
 
-        token
+        Span { start: end, end }
     }
 
-    pub fn parse(&mut self) {
-        let _ = span!(Level::TRACE, "Parsing").entered();
+    fn unexpected(&self, expected: Expected, found: Token) -> ParseError {
+        let kind = ParseErrorKind::UnexpectedToken {
+            expected,
+            found: found.ttype,
+        };
 
-        let main = self.parse_function_def();
+        self.error(kind, found)
+    }
+
+    /// Records an error so parsing can go on, unless it's at the same place as the previous one.
+    /// Those are almost always caused by the previous error, like when the input ends too early.
+    fn report(&mut self, error: ParseError) {
+        if self
+            .errors
+            .last()
+            .is_none_or(|last| last.span.start != error.span.start)
+        {
+            self.errors.push(error);
+        }
+    }
 
-        match self.tokens().get(self.cur_token) {
-            Some(token) => panic!(
-                "Cannot have top-level constructs ouside function: {}",
-                self.tokens().token_text(token.handle)
-            ),
-            None => (),
+    /// Skips tokens until somewhere parsing can resume after an error: right after a ';', or right
+    /// before a '}' or the start of a declaration
+    fn synchronize(&mut self) {
+        while let Some(token) = self.tokens().get(self.cur_token) {
+            match token.ttype {
+                TokenType::Semicolon => {
+                    self.cur_token += 1;
+                    return;
+                }
+                TokenType::CloseBrace => return,
+                ttype if starts_declaration(ttype) => return,
+                _ => self.cur_token += 1,
+            }
         }
-        let program_node = Program { main };
-        self.nodes.push::<Program, ProgramId>(program_node);
     }
 
-    fn parse_function_def(&mut self) -> FnDefId {
-        let _type_specifier = self.expect_keyword(Keyword::Int);
+    /// Skips tokens up to and including the '}' which closes the current block
+    fn skip_block(&mut self) {
+        let mut depth = 0;
+
+        while let Some(token) = self.tokens().get(self.cur_token) {
+            self.cur_token += 1;
+
+            match token.ttype {
+                TokenType::OpenBrace => depth += 1,
+                TokenType::CloseBrace if depth == 0 => return,
+                TokenType::CloseBrace => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+
+    /// Parses the whole input, failing with every error found if there's at least one
+    ///
+    /// Parsing goes on after errors, so the tree has as much of the program as could be parsed,
+    /// with error nodes where there were errors.
+    pub fn parse(&mut self) -> Result<(), ParseErrors> {
+        let _ = span!(Level::TRACE, "Parsing").entered();
+
+        let main = self.parse_function_def();
 
-        let function_name = self.expect_ident();
+        // Only a single function is supported for now
+        if let Some(token) = self.tokens().get(self.cur_token) {
+            self.report(self.unexpected(Expected::EndOfInput, token));
+        }
 
-        self.expect(TokenType::OpenParen);
-        self.expect_keyword(Keyword::Void);
-        self.expect(TokenType::CloseParen);
-        self.expect(TokenType::OpenBrace);
+        if let Some(main) = main {
+            let program_node = Program { main };
+            self.nodes.push::<Program, ProgramId>(program_node);
+        }
 
-        let stmt = self.parse_statement();
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseErrors {
+                errors: self.errors.clone(),
+            })
+        }
+    }
 
-        self.expect(TokenType::CloseBrace);
+    /// Parses a function, `None` if there was an error before its body since there's no function
+    /// to speak of then
+    fn parse_function_def(&mut self) -> Option<FnDefId> {
+        let function_name = match self.parse_function_header() {
+            Ok(name) => name,
+            Err(error) => {
+                self.report(error);
+                self.skip_function();
+                return None;
+            }
+        };
+
+        let body = self.parse_block_body();
 
         let fn_def = FnDef {
             name: function_name,
-            body: stmt,
+            body,
+        };
+
+        Some(self.nodes.push(fn_def))
+    }
+
+    /// <function-header> ::= "int" <identifier> "(" "void" ")" "{"
+    fn parse_function_header(&mut self) -> ParseResult<IdentId> {
+        let _type_specifier = self.expect_keyword(Keyword::Int)?;
+
+        let function_name = self.expect_ident()?;
+
+        self.expect(TokenType::OpenParen)?;
+        self.expect_keyword(Keyword::Void)?;
+        self.expect(TokenType::CloseParen)?;
+        self.expect(TokenType::OpenBrace)?;
+
+        Ok(function_name)
+    }
+
+    /// Skips the rest of a function whose header couldn't be parsed, up to the end of its body
+    ///
+    /// Declarations can't be synchronized at here, since parameters start like them.
+    fn skip_function(&mut self) {
+        while let Some(token) = self.tokens().get(self.cur_token) {
+            self.cur_token += 1;
+
+            if token.ttype == TokenType::OpenBrace {
+                self.skip_block();
+                return;
+            }
+        }
+    }
+
+    /// Parses the rest of a block whose '{' was already consumed, up to and including its '}'
+    fn parse_block_body(&mut self) -> BlockId {
+        let mut items = Vec::new();
+
+        while let Some(token) = self.tokens().get(self.cur_token)
+            && token.ttype != TokenType::CloseBrace
+        {
+            items.push(self.parse_block_item());
+        }
+
+        // The loop only stops at a '}' or the end of the input, so this fails only at the end
+        if let Err(error) = self.expect(TokenType::CloseBrace) {
+            self.report(error);
+        }
+
+        let items = self.nodes.push_block_items(&items);
+
+        self.nodes.push(Block { items })
+    }
+
+    /// <block-item> ::= <statement> | <declaration>
+    fn parse_block_item(&mut self) -> BlockItem {
+        let start = self.cur_token;
+
+        let item = match self.tokens().get(start) {
+            Some(token) if starts_declaration(token.ttype) => {
+                self.parse_declaration().map(BlockItem::Decl)
+            }
+            _ => self.parse_statement().map(BlockItem::Stmt),
+        };
+
+        item.unwrap_or_else(|error| BlockItem::Stmt(self.recover(error, start)))
+    }
+
+    /// Parses a statement, which is an error node if it couldn't be parsed
+    fn parse_statement_or_error(&mut self) -> StmtId {
+        let start = self.cur_token;
+
+        self.parse_statement()
+            .unwrap_or_else(|error| self.recover(error, start))
+    }
+
+    /// Reports an error in the item which started at `start` and skips the rest of it, returning
+    /// the error node which stands for it
+    fn recover(&mut self, error: ParseError, start: usize) -> StmtId {
+        self.report(error);
+
+        // Errors are always at the current token, synchronizing would stop right there if it
+        // starts a declaration
+        if let Some(token) = self.tokens().get(self.cur_token)
+            && starts_declaration(token.ttype)
+        {
+            self.cur_token += 1;
+        }
+        self.synchronize();
+        let span = self.skipped_span(start);
+
+        self.nodes.push(Stmt::Error { span })
+    }
+
+    /// <declaration> ::= "int" <identifier> [ "=" <expr> ] ";"
+    fn parse_declaration(&mut self) -> ParseResult<DeclId> {
+        let _type_specifier = self.expect_keyword(Keyword::Int)?;
+
+        let name = self.expect_ident()?;
+
+        let init = match self.peek(Expected::Token(TokenType::Semicolon))? {
+            token if token.ttype == TokenType::Equal => {
+                self.cur_token += 1;
+                Some(self.parse_expr()?)
+            }
+            _ => None,
+        };
+
+        self.expect(TokenType::Semicolon)?;
+
+        Ok(self.nodes.push(Decl { name, init }))
+    }
+
+    /// <statement> ::= "return" <expr> ";" | <if> | "{" { <block-item> } "}" | <expr> ";" | ";"
+    fn parse_statement(&mut self) -> ParseResult<StmtId> {
+        let token = self.peek(Expected::Statement)?;
+
+        match token.ttype {
+            TokenType::Keyword(Keyword::Return) => self.parse_return(),
+            TokenType::Keyword(Keyword::If) => self.parse_if(),
+            TokenType::OpenBrace => {
+                self.cur_token += 1;
+                let block = self.parse_block_body();
+
+                Ok(self.nodes.push(Stmt::Compound { block }))
+            }
+            TokenType::Semicolon => {
+                self.cur_token += 1;
+                Ok(self.nodes.push(Stmt::Null))
+            }
+            _ => {
+                let expr = self.parse_expr()?;
+                self.expect(TokenType::Semicolon)?;
+
+                Ok(self.nodes.push(Stmt::Expression { expr }))
+            }
+        }
+    }
+
+    /// Span of the tokens from `start` up to the current one, empty if there are none
+    fn skipped_span(&self, start: usize) -> Span {
+        let span = |i| self.tokens().token_source(i).span;
+
+        match self.cur_token.checked_sub(1) {
+            Some(last) if last >= start => span(start).to(span(last)),
+            _ if start < self.tokens().len() => {
+                let start = span(start).start;
+                Span { start, end: start }
+            }
+            _ => self.end_span(),
+        }
+    }
+
+    /// <if> ::= "if" "(" <expr> ")" <statement> [ "else" <statement> ]
+    ///
+    /// An `else` belongs to the closest `if`, since that's the one parsing its statement when it
+    /// shows up.
+    fn parse_if(&mut self) -> ParseResult<StmtId> {
+        self.expect_keyword(Keyword::If)?;
+
+        self.expect(TokenType::OpenParen)?;
+        let cond = self.parse_expr()?;
+        self.expect(TokenType::CloseParen)?;
+
+        let cond_true = self.parse_statement_or_error();
+
+        let cond_false = match self.tokens().get(self.cur_token) {
+            Some(token) if token.ttype == TokenType::Keyword(Keyword::Else) => {
+                self.cur_token += 1;
+                Some(self.parse_statement_or_error())
+            }
+            _ => None,
+        };
+
+        let stmt = Stmt::If {
+            cond,
+            cond_true,
+            cond_false,
         };
 
-        self.nodes.push(fn_def)
+        Ok(self.nodes.push(stmt))
     }
 
     /// <statement> ::= "return" <expr> ";"
-    fn parse_statement(&mut self) -> StmtId {
-        let return_keyword = self.expect_keyword(Keyword::Return);
+    fn parse_return(&mut self) -> ParseResult<StmtId> {
+        let return_keyword = self.expect_keyword(Keyword::Return)?;
 
-        let return_expr = self.parse_expr();
+        let return_expr = self.parse_expr()?;
 
-        self.expect(TokenType::Semicolon);
+        self.expect(TokenType::Semicolon)?;
 
         let stmt = Stmt::Return {
             expr: return_expr,
             token: return_keyword,
         };
 
-        self.nodes.push(stmt)
+        Ok(self.nodes.push(stmt))
+    }
+
+    /// <expr> ::= <factor> | <expr> <binop> <expr> | <expr> <assignop> <expr>
+    ///          | <expr> "?" <expr> ":" <expr>
+    fn parse_expr(&mut self) -> ParseResult<ExprId> {
+        self.parse_binary(0)
+    }
+
+    /// Parses an expression whose operators all bind at least as tightly as `min_precedence`, by
+    /// precedence climbing
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<ExprId> {
+        let mut left = self.parse_factor()?;
+
+        while let Some(token) = self.tokens().get(self.cur_token) {
+            let compound = compound_assign_op(token.ttype);
+
+            if (token.ttype == TokenType::Equal || compound.is_some())
+                && ASSIGN_PRECEDENCE >= min_precedence
+            {
+                self.cur_token += 1;
+
+                // Assignment is right associative, so the right operand takes it too
+                let src = self.parse_binary(ASSIGN_PRECEDENCE)?;
+                let dst = left;
+
+                left = self.nodes.push(match compound {
+                    Some(op) => Expr::CompoundAssign {
+                        op,
+                        dst,
+                        src,
+                        token,
+                    },
+                    None => Expr::Assign { dst, src, token },
+                });
+                continue;
+            }
+
+            if token.ttype == TokenType::Question && CONDITIONAL_PRECEDENCE >= min_precedence {
+                self.cur_token += 1;
+
+                // Anything goes between '?' and ':', like in parentheses
+                let cond_true = self.parse_expr()?;
+                self.expect(TokenType::Colon)?;
+                // Right associative too
+                let cond_false = self.parse_binary(CONDITIONAL_PRECEDENCE)?;
+
+                left = self.nodes.push(Expr::Conditional {
+                    cond: left,
+                    cond_true,
+                    cond_false,
+                });
+                continue;
+            }
+
+            let Some(op) = binary_op(token.ttype) else {
+                break;
+            };
+            if precedence(op) < min_precedence {
+                break;
+            }
+            self.cur_token += 1;
+
+            // Operators are left associative, so the right operand only takes tighter ones
+            let right = self.parse_binary(precedence(op) + 1)?;
+            left = self.nodes.push(Expr::Binary { op, left, right });
+        }
+
+        Ok(left)
+    }
+
+    /// <factor> ::= <unop> <factor> | ( "++" | "--" ) <factor> | <postfix>
+    fn parse_factor(&mut self) -> ParseResult<ExprId> {
+        let token = self.peek(Expected::Expression)?;
+
+        let expr = match token.ttype {
+            TokenType::Increment | TokenType::Decrement => {
+                self.cur_token += 1;
+
+                Expr::Increment {
+                    op: increment_op(token.ttype),
+                    fixity: Fixity::Prefix,
+                    operand: self.parse_factor()?,
+                    token,
+                }
+            }
+            TokenType::Hyphen | TokenType::Tilde | TokenType::Bang => {
+                self.cur_token += 1;
+
+                let op = match token.ttype {
+                    TokenType::Hyphen => UnaryOp::Negate,
+                    TokenType::Tilde => UnaryOp::Complement,
+                    _ => UnaryOp::Not,
+                };
+                let operand = self.parse_factor()?;
+
+                Expr::Unary { op, operand }
+            }
+            _ => return self.parse_postfix(),
+        };
+
+        Ok(self.nodes.push(expr))
+    }
+
+    /// <postfix> ::= <primary> { "++" | "--" }
+    fn parse_postfix(&mut self) -> ParseResult<ExprId> {
+        let mut expr = self.parse_primary()?;
+
+        while let Some(token) = self.tokens().get(self.cur_token)
+            && matches!(token.ttype, TokenType::Increment | TokenType::Decrement)
+        {
+            self.cur_token += 1;
+
+            expr = self.nodes.push(Expr::Increment {
+                op: increment_op(token.ttype),
+                fixity: Fixity::Postfix,
+                operand: expr,
+                token,
+            });
+        }
+
+        Ok(expr)
     }
 
-    /// <expr> ::= <constant>
-    fn parse_expr(&mut self) -> ExprId {
-        let value = self.parse_constant();
-        let expr = Expr::Constant { constant: value };
-        self.nodes.push(expr)
+    /// <primary> ::= <constant> | <identifier> | "(" <expr> ")"
+    fn parse_primary(&mut self) -> ParseResult<ExprId> {
+        let token = self.peek(Expected::Expression)?;
+
+        let expr = match token.ttype {
+            TokenType::IntConstant { .. } => Expr::Constant {
+                constant: self.parse_constant()?,
+            },
+            TokenType::Ident => Expr::Var {
+                ident: self.expect_ident()?,
+            },
+            TokenType::OpenParen => {
+                self.cur_token += 1;
+
+                let expr = self.parse_expr()?;
+                self.expect(TokenType::CloseParen)?;
+
+                return Ok(expr);
+            }
+            _ => return Err(self.unexpected(Expected::Expression, token)),
+        };
+
+        Ok(self.nodes.push(expr))
     }
 
     /// <constant> = <int>
-    fn parse_constant(&mut self) -> ConstantId {
-        let token = self.expect(TokenType::Constant);
-        let token_source = self.tokens().token_text(token.handle);
-        let value: i64 = token_source.parse().unwrap();
+    fn parse_constant(&mut self) -> ParseResult<ConstantId> {
+        let token = self.peek(Expected::Constant)?;
+
+        if !matches!(token.ttype, TokenType::IntConstant { .. }) {
+            return Err(self.unexpected(Expected::Constant, token));
+        }
+        self.cur_token += 1;
+
+        let value = self.tokens().int_value(token.handle);
+        let value = i64::try_from(value)
+            .map_err(|_| self.error(ParseErrorKind::ConstantTooLarge { value }, token))?;
 
         let constant = Constant { value, token };
 
-        self.nodes.push(constant)
+        Ok(self.nodes.push(constant))
+    }
+}
+
+/// Binary operator a token of type `ttype` stands for, if any
+fn binary_op(ttype: TokenType) -> Option<BinaryOp> {
+    let op = match ttype {
+        TokenType::Plus => BinaryOp::Add,
+        TokenType::Hyphen => BinaryOp::Subtract,
+        TokenType::Asterisk => BinaryOp::Multiply,
+        TokenType::FrontSlash => BinaryOp::Divide,
+        TokenType::Percent => BinaryOp::Remainder,
+        TokenType::Ampersand => BinaryOp::BitAnd,
+        TokenType::Pipe => BinaryOp::BitOr,
+        TokenType::Caret => BinaryOp::BitXor,
+        TokenType::ShiftLeft => BinaryOp::ShiftLeft,
+        TokenType::ShiftRight => BinaryOp::ShiftRight,
+        TokenType::LogicalAnd => BinaryOp::And,
+        TokenType::LogicalOr => BinaryOp::Or,
+        TokenType::EqualEqual => BinaryOp::Equal,
+        TokenType::NotEqual => BinaryOp::NotEqual,
+        TokenType::Less => BinaryOp::LessThan,
+        TokenType::LessEqual => BinaryOp::LessOrEqual,
+        TokenType::Greater => BinaryOp::GreaterThan,
+        TokenType::GreaterEqual => BinaryOp::GreaterOrEqual,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Operator of a compound assignment token, like `Add` for '+='
+fn compound_assign_op(ttype: TokenType) -> Option<BinaryOp> {
+    let op = match ttype {
+        TokenType::PlusEqual => BinaryOp::Add,
+        TokenType::HyphenEqual => BinaryOp::Subtract,
+        TokenType::AsteriskEqual => BinaryOp::Multiply,
+        TokenType::FrontSlashEqual => BinaryOp::Divide,
+        TokenType::PercentEqual => BinaryOp::Remainder,
+        TokenType::AmpersandEqual => BinaryOp::BitAnd,
+        TokenType::PipeEqual => BinaryOp::BitOr,
+        TokenType::CaretEqual => BinaryOp::BitXor,
+        TokenType::ShiftLeftEqual => BinaryOp::ShiftLeft,
+        TokenType::ShiftRightEqual => BinaryOp::ShiftRight,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Operator of a '++' or '--' token
+fn increment_op(ttype: TokenType) -> IncrementOp {
+    match ttype {
+        TokenType::Increment => IncrementOp::Increment,
+        _ => IncrementOp::Decrement,
     }
 }
 
+/// Precedence of assignments, which bind looser than every binary operator
+const ASSIGN_PRECEDENCE: u8 = 1;
+
+/// Precedence of '?', which binds tighter than assignments but looser than binary operators
+const CONDITIONAL_PRECEDENCE: u8 = 3;
+
+/// How tightly `op` binds its operands, higher binds tighter
+fn precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => 5,
+        BinaryOp::And => 10,
+        BinaryOp::BitOr => 15,
+        BinaryOp::BitXor => 20,
+        BinaryOp::BitAnd => 25,
+        BinaryOp::Equal | BinaryOp::NotEqual => 30,
+        BinaryOp::LessThan
+        | BinaryOp::LessOrEqual
+        | BinaryOp::GreaterThan
+        | BinaryOp::GreaterOrEqual => 35,
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 40,
+        BinaryOp::Add | BinaryOp::Subtract => 45,
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => 50,
+    }
+}
+
+/// Whether a declaration can start with a token of type `ttype`
+fn starts_declaration(ttype: TokenType) -> bool {
+    matches!(
+        ttype,
+        TokenType::Keyword(
+            Keyword::Alignas
+                | Keyword::Atomic
+                | Keyword::Auto
+                | Keyword::Bool
+                | Keyword::Char
+                | Keyword::Complex
+                | Keyword::Const
+                | Keyword::Double
+                | Keyword::Enum
+                | Keyword::Extern
+                | Keyword::Float
+                | Keyword::Inline
+                | Keyword::Int
+                | Keyword::Long
+                | Keyword::Noreturn
+                | Keyword::Register
+                | Keyword::Restrict
+                | Keyword::Short
+                | Keyword::Signed
+                | Keyword::Static
+                | Keyword::StaticAssert
+                | Keyword::Struct
+                | Keyword::ThreadLocal
+                | Keyword::Typedef
+                | Keyword::Union
+                | Keyword::Unsigned
+                | Keyword::Void
+                | Keyword::Volatile
+        )
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use lex::{Lexer, TokenizedOutput};
+    use ast::{BlockItem, Expr, ExprId, NodeKind, Stmt, UnaryOp};
+    use lex::{Lexer, TokenType, token::Span};
 
-    use crate::Parser;
+    use crate::{Expected, ParseError, ParseErrorKind, Parser};
 
     #[test]
     fn parse_zero() {
@@ -145,7 +691,7 @@ mod tests {
         let tokens = Lexer::lex(source).unwrap();
         let mut parser = Parser::from_tokens(tokens);
 
-        let constant_id = parser.parse_constant();
+        let constant_id = parser.parse_constant().unwrap();
         assert_eq!(usize::from(constant_id), 0);
 
         let constant = parser.nodes[constant_id];
@@ -160,7 +706,7 @@ mod tests {
         let tokens = Lexer::lex(source).unwrap();
         let mut parser = Parser::from_tokens(tokens);
 
-        let constant_id = parser.parse_constant();
+        let constant_id = parser.parse_constant().unwrap();
         assert_eq!(usize::from(constant_id), 0);
 
         let constant = parser.nodes[constant_id];
@@ -175,7 +721,7 @@ mod tests {
         let tokens = Lexer::lex(&source).unwrap();
         let mut parser = Parser::from_tokens(tokens);
 
-        let constant_id = parser.parse_constant();
+        let constant_id = parser.parse_constant().unwrap();
         assert_eq!(usize::from(constant_id), 0);
 
         let constant = parser.nodes[constant_id];
@@ -183,32 +729,445 @@ mod tests {
         assert_eq!(constant.token, parser.tokens().get(0).unwrap());
     }
 
-    // FIXME: These tests exercise negative number parsing which we don't handle just yet
-    // #[test]
-    // fn parse_minus_one() {
-    //     let source = format!("{}", -1);
-    //
-    //     let mut parser = lex(&source);
-    //
-    //     let constant_id = parser.parse_constant();
-    //     assert_eq!(usize::from(constant_id), 0);
-    //
-    //     let constant = parser.nodes[constant_id];
-    //     assert_eq!(constant.value, -1);
-    //     assert_eq!(constant.token, parser.input.get(0).unwrap());
-    // }
-    //
-    // #[test]
-    // fn parse_i64_min() {
-    //     let source = format!("{}", i64::MIN);
-    //
-    //     let mut parser = lex(&source);
-    //
-    //     let constant_id = parser.parse_constant();
-    //     assert_eq!(usize::from(constant_id), 0);
-    //
-    //     let constant = parser.nodes[constant_id];
-    //     assert_eq!(constant.value, i64::MIN);
-    //     assert_eq!(constant.token, parser.input.get(0).unwrap());
-    // }
+    #[test]
+    fn parse_hex() {
+        let source = "0x2A";
+
+        let tokens = Lexer::lex(source).unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+
+        let constant_id = parser.parse_constant().unwrap();
+
+        let constant = parser.nodes[constant_id];
+        assert_eq!(constant.value, 42);
+        assert_eq!(constant.token, parser.tokens().get(0).unwrap());
+    }
+
+    /// Parses `source` as a whole program, which must fail with a single error
+    fn parse_err(source: &str) -> ParseError {
+        let mut errors = parse_errors(source);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+
+        errors.remove(0)
+    }
+
+    /// Parses `source` as a whole program, which must fail
+    fn parse_errors(source: &str) -> Vec<ParseError> {
+        let tokens = Lexer::lex(source).unwrap();
+
+        Parser::from_tokens(tokens).parse().unwrap_err().errors
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let error = parse_err("int main(void) {\n    return 0\n}");
+
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedToken {
+                expected: Expected::Token(TokenType::Semicolon),
+                found: TokenType::CloseBrace
+            }
+        );
+        assert_eq!(error.span, Span { start: 30, end: 31 });
+        assert_eq!(error.to_string(), "Expected [Semicolon], got [CloseBrace]");
+    }
+
+    #[test]
+    fn unexpected_eof() {
+        let source = "int main(void) {\n    return";
+        let error = parse_err(source);
+
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedEof {
+                expected: Expected::Expression
+            }
+        );
+        assert_eq!(
+            error.span,
+            Span {
+                start: source.len(),
+                end: source.len()
+            }
+        );
+    }
+
+    #[test]
+    fn extra_tokens() {
+        let error = parse_err("int main(void) { return 2; } foo");
+
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedToken {
+                expected: Expected::EndOfInput,
+                found: TokenType::Ident
+            }
+        );
+        assert_eq!(error.to_string(), "Expected end of input, got [Ident]");
+    }
+
+    #[test]
+    fn constant_too_large() {
+        let error = parse_err("int main(void) { return 9223372036854775808; }");
+
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::ConstantTooLarge {
+                value: 9223372036854775808
+            }
+        );
+    }
+
+    #[test]
+    fn recovery() {
+        let source = "int main(void) {\n    return int;\n}\nfoo";
+        let tokens = Lexer::lex(source).unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        let errors = parser.parse().unwrap_err().errors;
+
+        // The missing '}' is at the same token as the first error, so it's not reported again
+        let messages: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected expression, got [Keyword(int)]",
+                "Expected end of input, got [Ident]"
+            ]
+        );
+
+        // The statement is still in the tree, as an error
+        let error = parser.nodes.nodes.iter().find_map(|node| match node.kind {
+            NodeKind::Stmt(Stmt::Error { span }) => Some(span),
+            _ => None,
+        });
+        assert_eq!(error, Some(Span { start: 21, end: 32 }));
+        assert_eq!(parser.errors().len(), 2);
+    }
+
+    #[test]
+    fn recovery_in_header() {
+        let errors = parse_errors("int 3 (void) {\n    return 0;\n}\n}");
+        let messages: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(
+            messages,
+            [
+                "Expected [Ident], got [IntConstant]",
+                "Expected end of input, got [CloseBrace]"
+            ]
+        );
+    }
+
+    #[test]
+    fn synchronize_at_semicolon() {
+        let error = parse_err("int main(void) { RETURN 0 + ; }");
+
+        assert_eq!(error.to_string(), "Expected [Semicolon], got [IntConstant]");
+    }
+
+    #[test]
+    fn parse_minus_one() {
+        let tokens = Lexer::lex("-1").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+
+        let expr_id = parser.parse_expr().unwrap();
+
+        let Expr::Unary {
+            op: UnaryOp::Negate,
+            operand,
+        } = parser.nodes[expr_id]
+        else {
+            panic!("Expected a negation, got {:?}", parser.nodes[expr_id]);
+        };
+        let Expr::Constant { constant } = parser.nodes[operand] else {
+            panic!("Expected a constant, got {:?}", parser.nodes[operand]);
+        };
+        assert_eq!(parser.nodes[constant].value, 1);
+    }
+
+    #[test]
+    fn parse_i64_min() {
+        // The constant is parsed before being negated, and it doesn't fit on its own
+        let source = format!("{}", i64::MIN);
+
+        let tokens = Lexer::lex(&source).unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+
+        let error = parser.parse_expr().unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::ConstantTooLarge {
+                value: i64::MIN.unsigned_abs()
+            }
+        );
+    }
+
+    #[test]
+    fn nested_unary() {
+        let tokens = Lexer::lex("int main(void) { return -(~(!2)); }").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+
+        let ops: Vec<_> = parser
+            .nodes
+            .nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Expr(Expr::Unary { op, .. }) => Some(op),
+                _ => None,
+            })
+            .collect();
+
+        // Subexpressions are pushed first
+        assert_eq!(ops, [UnaryOp::Not, UnaryOp::Complement, UnaryOp::Negate]);
+    }
+
+    #[test]
+    fn unary_without_operand() {
+        let error = parse_err("int main(void) { return (-)3; }");
+
+        assert_eq!(error.to_string(), "Expected expression, got [CloseParen]");
+    }
+
+    /// Parses `source` as an expression, writing it back with every operation in parentheses
+    fn parenthesize(source: &str) -> String {
+        fn write(parser: &Parser, expr: ExprId) -> String {
+            match parser.nodes[expr] {
+                Expr::Constant { constant } => parser.nodes[constant].value.to_string(),
+                Expr::Var { ident } => parser.tokens().symbols()[parser.nodes[ident].name].into(),
+                Expr::Assign { dst, src, .. } => {
+                    format!("({} = {})", write(parser, dst), write(parser, src))
+                }
+                Expr::CompoundAssign { op, dst, src, .. } => {
+                    format!("({} {op}= {})", write(parser, dst), write(parser, src))
+                }
+                Expr::Increment {
+                    op,
+                    fixity,
+                    operand,
+                    ..
+                } => format!("({fixity}{op} {})", write(parser, operand)),
+                Expr::Conditional {
+                    cond,
+                    cond_true,
+                    cond_false,
+                } => format!(
+                    "({} ? {} : {})",
+                    write(parser, cond),
+                    write(parser, cond_true),
+                    write(parser, cond_false)
+                ),
+                Expr::Unary { op, operand } => format!("({op} {})", write(parser, operand)),
+                Expr::Binary { op, left, right } => {
+                    format!("({} {op} {})", write(parser, left), write(parser, right))
+                }
+            }
+        }
+
+        let tokens = Lexer::lex(source).unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        let expr = parser.parse_expr().unwrap();
+        assert_eq!(parser.tokens().get(parser.cur_token), None);
+
+        write(&parser, expr)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            parenthesize("1 + 2 * 3 - 4 / 5 % 6"),
+            "((1 Add (2 Multiply 3)) Subtract ((4 Divide 5) Remainder 6))"
+        );
+        assert_eq!(
+            parenthesize("(1 + 2) * -3"),
+            "((1 Add 2) Multiply (Negate 3))"
+        );
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(parenthesize("1 - 2 - 3"), "((1 Subtract 2) Subtract 3)");
+        assert_eq!(parenthesize("8 / 4 * 2"), "((8 Divide 4) Multiply 2)");
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        assert_eq!(
+            parenthesize("1 | 2 ^ 3 & 4 << 5 + 6"),
+            "(1 BitOr (2 BitXor (3 BitAnd (4 ShiftLeft (5 Add 6)))))"
+        );
+        assert_eq!(
+            parenthesize("1 << 2 >> 3"),
+            "((1 ShiftLeft 2) ShiftRight 3)"
+        );
+    }
+
+    #[test]
+    fn logical_precedence() {
+        assert_eq!(
+            parenthesize("1 || 2 && 3 == 4 < 5 | 6"),
+            "(1 Or (2 And ((3 Equal (4 LessThan 5)) BitOr 6)))"
+        );
+        assert_eq!(
+            parenthesize("1 < 2 != 3 >= 4"),
+            "((1 LessThan 2) NotEqual (3 GreaterOrEqual 4))"
+        );
+        assert_eq!(parenthesize("!1 || 2 & 3"), "((Not 1) Or (2 BitAnd 3))");
+    }
+
+    #[test]
+    fn unary_binds_tighter() {
+        assert_eq!(parenthesize("-1 + ~2"), "((Negate 1) Add (Complement 2))");
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(parenthesize("a = b = 1 + 2"), "(a = (b = (1 Add 2)))");
+        assert_eq!(parenthesize("a = 1 || b"), "(a = (1 Or b))");
+        // Only the semantic analysis knows this can't be assigned to
+        assert_eq!(parenthesize("1 + a = 2"), "((1 Add a) = 2)");
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(
+            parenthesize("a += b -= 2 * 3"),
+            "(a Add= (b Subtract= (2 Multiply 3)))"
+        );
+        assert_eq!(
+            parenthesize("a <<= b >>= c | 1"),
+            "(a ShiftLeft= (b ShiftRight= (c BitOr 1)))"
+        );
+    }
+
+    #[test]
+    fn increment() {
+        // Postfix operators bind tighter than prefix ones
+        assert_eq!(
+            parenthesize("-a++ + --b"),
+            "((Negate (PostfixIncrement a)) Add (PrefixDecrement b))"
+        );
+        assert_eq!(
+            parenthesize("++(a)--"),
+            "(PrefixIncrement (PostfixDecrement a))"
+        );
+        // Only the semantic analysis knows these can't be incremented
+        assert_eq!(parenthesize("5++"), "(PostfixIncrement 5)");
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!(
+            parenthesize("a = 1 || 2 ? b = 3 : 4 ? 5 : 6"),
+            "(a = ((1 Or 2) ? (b = 3) : (4 ? 5 : 6)))"
+        );
+        // Only the semantic analysis knows this can't be assigned to
+        assert_eq!(parenthesize("1 ? 2 : a = 3"), "((1 ? 2 : a) = 3)");
+    }
+
+    #[test]
+    fn dangling_else() {
+        let tokens =
+            Lexer::lex("int main(void) { if (1) if (2) return 3; else return 4; }").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+
+        let ifs: Vec<_> = parser
+            .nodes
+            .nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Stmt(Stmt::If { cond_false, .. }) => Some(cond_false.is_some()),
+                _ => None,
+            })
+            .collect();
+
+        // The inner `if`, which is pushed first, is the one with the `else`
+        assert_eq!(ifs, [true, false]);
+    }
+
+    #[test]
+    fn recovery_in_if() {
+        let errors = parse_errors("int main(void) { if (1) return; else return 2 return 3; }");
+        let messages: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(
+            messages,
+            [
+                "Expected expression, got [Semicolon]",
+                "Expected [Semicolon], got [Keyword(return)]"
+            ]
+        );
+    }
+
+    #[test]
+    fn block_items() {
+        let tokens = Lexer::lex("int main(void) { int a; int b = 2; a = b; ; return a; }").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+
+        let NodeKind::Program(program) = parser.nodes.program_node().kind else {
+            unreachable!()
+        };
+        let body = parser.nodes[parser.nodes[parser.nodes[program.main].body].items].to_vec();
+
+        let [
+            BlockItem::Decl(a),
+            BlockItem::Decl(b),
+            BlockItem::Stmt(assign),
+            BlockItem::Stmt(null),
+            BlockItem::Stmt(ret),
+        ] = body[..]
+        else {
+            panic!("Unexpected block items {body:?}");
+        };
+
+        assert_eq!(parser.nodes[a].init, None);
+        assert!(parser.nodes[b].init.is_some());
+        assert!(matches!(parser.nodes[assign], Stmt::Expression { .. }));
+        assert_eq!(parser.nodes[null], Stmt::Null);
+        assert!(matches!(parser.nodes[ret], Stmt::Return { .. }));
+    }
+
+    #[test]
+    fn declaration_without_name() {
+        let error = parse_err("int main(void) { int 10 = 0; return 10; }");
+
+        assert_eq!(error.to_string(), "Expected [Ident], got [IntConstant]");
+    }
+
+    #[test]
+    fn binary_without_operand() {
+        let error = parse_err("int main(void) { return 1 + ; }");
+
+        assert_eq!(error.to_string(), "Expected expression, got [Semicolon]");
+    }
+
+    #[test]
+    fn nested_blocks() {
+        let tokens = Lexer::lex("int main(void) { { int a; { } } if (1) { ; } }").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+
+        let sizes: Vec<_> = parser
+            .nodes
+            .nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Block(block) => Some(parser.nodes[block.items].len()),
+                _ => None,
+            })
+            .collect();
+
+        // Inner blocks are pushed before the blocks containing them
+        assert_eq!(sizes, [0, 2, 1, 2]);
+    }
+
+    #[test]
+    fn unclosed_block() {
+        let error = parse_err("int main(void) { { return 0; }");
+
+        assert_eq!(error.to_string(), "Expected [CloseBrace], got end of input");
+    }
 }
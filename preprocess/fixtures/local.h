@@ -0,0 +1,2 @@
+int local = 1;
+#define LOCAL 1
@@ -0,0 +1,178 @@
+//! Conditional inclusion: `#if`, `#ifdef`, `#ifndef`, `#elif`, `#else` and `#endif`
+
+use lex::{
+    TokenType,
+    token::{IntSuffix, Radix},
+};
+
+use crate::{PreprocessErrorKind, PreprocessResult, Preprocessor, SourceFile, token::PpToken};
+
+/// A conditional directive which hasn't reached its `#endif` yet
+#[derive(Debug, Clone)]
+pub(crate) struct Conditional {
+    /// The '#' of the directive which opened it, used for error messages
+    pub(crate) hash: PpToken,
+    /// Name of the directive which opened it
+    pub(crate) directive: String,
+    /// Whether one of its groups has already been included, in which case every other is skipped
+    included: bool,
+    /// Whether its `#else` was already found
+    in_else: bool,
+}
+
+impl Preprocessor<'_> {
+    /// Handles a conditional directive called `name`, skipping any group which must not be
+    /// included
+    pub(crate) fn conditional(
+        &mut self,
+        source: &mut SourceFile,
+        hash: PpToken,
+        name: &PpToken,
+        args: &[PpToken],
+    ) -> PreprocessResult<()> {
+        let directive = name.text.as_str();
+
+        if matches!(directive, "if" | "ifdef" | "ifndef") {
+            let included = match directive {
+                "if" => self.evaluate_condition(name, args)?,
+                _ => {
+                    let defined = self.macros.contains_key(&self.macro_name(name, args)?.text);
+                    defined == (directive == "ifdef")
+                }
+            };
+
+            source.conditionals.push(Conditional {
+                hash,
+                directive: directive.to_string(),
+                included,
+                in_else: false,
+            });
+
+            if !included {
+                skip_group(&mut source.input);
+            }
+            return Ok(());
+        }
+
+        let Some(conditional) = source.conditionals.last_mut() else {
+            return Err(self.error(
+                PreprocessErrorKind::UnmatchedConditional {
+                    directive: directive.to_string(),
+                },
+                &hash,
+            ));
+        };
+
+        if directive == "endif" {
+            source.conditionals.pop();
+            return Ok(());
+        }
+
+        if conditional.in_else {
+            return Err(self.error(
+                PreprocessErrorKind::ElseAfterElse {
+                    directive: directive.to_string(),
+                },
+                &hash,
+            ));
+        }
+
+        let included = if conditional.included {
+            false
+        } else if directive == "elif" {
+            self.evaluate_condition(name, args)?
+        } else {
+            true
+        };
+
+        conditional.included |= included;
+        conditional.in_else = directive == "else";
+
+        if !included {
+            skip_group(&mut source.input);
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the condition of an `#if` or `#elif`
+    fn evaluate_condition(
+        &mut self,
+        directive: &PpToken,
+        args: &[PpToken],
+    ) -> PreprocessResult<bool> {
+        let mut tokens = Vec::with_capacity(args.len());
+        let mut args = args.iter();
+
+        // `defined` has to be handled before expanding macros, since the macro names it refers to
+        // would be expanded otherwise
+        while let Some(token) = args.next() {
+            if token.text != "defined" {
+                tokens.push(token.clone());
+                continue;
+            }
+
+            let mut name = args.next();
+            let parens = name.is_some_and(|t| t.ttype == TokenType::OpenParen);
+            if parens {
+                name = args.next();
+            }
+
+            let name = match name {
+                Some(name) if name.is_ident() => name,
+                _ => return Err(self.error(PreprocessErrorKind::MissingMacroName, token)),
+            };
+
+            if parens {
+                match args.next() {
+                    Some(paren) if paren.ttype == TokenType::CloseParen => (),
+                    paren => {
+                        return Err(self.error(
+                            PreprocessErrorKind::ExpectedToken { expected: ')' },
+                            paren.unwrap_or(name),
+                        ));
+                    }
+                }
+            }
+
+            tokens.push(PpToken {
+                ttype: TokenType::IntConstant {
+                    radix: Radix::Decimal,
+                    suffix: IntSuffix::None,
+                },
+                text: (self.macros.contains_key(&name.text) as u8).to_string(),
+                ..token.clone()
+            });
+        }
+
+        let tokens = self.expand_all(tokens)?;
+
+        Ok(self.evaluate(directive, &tokens)? != 0)
+    }
+}
+
+/// Pops the tokens of a group which isn't included, stopping right before the `#elif`, `#else` or
+/// `#endif` which ends it
+///
+/// Nested conditionals are skipped entirely, without evaluating any of their conditions.
+fn skip_group(input: &mut Vec<PpToken>) {
+    let mut depth = 0;
+
+    while let Some(token) = input.last() {
+        let name = input.len().checked_sub(2).map(|i| &input[i]);
+
+        if token.ttype == TokenType::Hash
+            && token.at_line_start
+            && let Some(name) = name.filter(|name| !name.at_line_start)
+        {
+            match name.text.as_str() {
+                "if" | "ifdef" | "ifndef" => depth += 1,
+                "elif" | "else" | "endif" if depth == 0 => return,
+                "endif" => depth -= 1,
+                _ => (),
+            }
+        }
+
+        input.pop();
+    }
+}
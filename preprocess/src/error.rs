@@ -0,0 +1,150 @@
+use std::{error::Error, fmt::Display};
+
+use crate::token::Location;
+
+/// A preprocessing error and where it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    pub kind: PreprocessErrorKind,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessErrorKind {
+    UnknownDirective {
+        name: String,
+    },
+    MissingMacroName,
+    InvalidMacroParams,
+    DuplicateMacroParam {
+        name: String,
+    },
+    HashWithoutParam,
+    PasteAtEdge,
+    MacroRedefined {
+        name: String,
+    },
+    InvalidPaste {
+        left: String,
+        right: String,
+    },
+    UnterminatedMacroCall {
+        name: String,
+    },
+    WrongArgCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidInclude,
+    IncludeNotFound {
+        name: String,
+    },
+    IncludeTooDeep,
+    Io {
+        message: String,
+    },
+    UnterminatedConditional {
+        directive: String,
+    },
+    UnmatchedConditional {
+        directive: String,
+    },
+    ElseAfterElse {
+        directive: String,
+    },
+    ErrorDirective {
+        message: String,
+    },
+    MissingExpression,
+    InvalidExpressionToken {
+        token: String,
+    },
+    ExpectedToken {
+        expected: char,
+    },
+    DivisionByZero,
+    InvalidLineDirective,
+}
+
+impl Display for PreprocessErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreprocessErrorKind::UnknownDirective { name } => {
+                write!(f, "Invalid preprocessing directive '#{name}'")
+            }
+            PreprocessErrorKind::MissingMacroName => write!(f, "Macro names must be identifiers"),
+            PreprocessErrorKind::InvalidMacroParams => write!(f, "Invalid macro parameter list"),
+            PreprocessErrorKind::DuplicateMacroParam { name } => {
+                write!(f, "Duplicate macro parameter '{name}'")
+            }
+            PreprocessErrorKind::HashWithoutParam => {
+                write!(f, "'#' is not followed by a macro parameter")
+            }
+            PreprocessErrorKind::PasteAtEdge => {
+                write!(f, "'##' cannot appear at either end of a macro expansion")
+            }
+            PreprocessErrorKind::MacroRedefined { name } => write!(f, "'{name}' macro redefined"),
+            PreprocessErrorKind::InvalidPaste { left, right } => write!(
+                f,
+                "Pasting \"{left}\" and \"{right}\" does not give a valid preprocessing token"
+            ),
+            PreprocessErrorKind::UnterminatedMacroCall { name } => {
+                write!(f, "Unterminated argument list invoking macro '{name}'")
+            }
+            PreprocessErrorKind::WrongArgCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Macro '{name}' expects {expected} arguments, but {found} were given"
+            ),
+            PreprocessErrorKind::InvalidInclude => {
+                write!(f, "#include expects \"FILENAME\" or <FILENAME>")
+            }
+            PreprocessErrorKind::IncludeNotFound { name } => write!(f, "'{name}' file not found"),
+            PreprocessErrorKind::IncludeTooDeep => write!(f, "#include nested too deeply"),
+            PreprocessErrorKind::Io { message } => write!(f, "{message}"),
+            PreprocessErrorKind::UnterminatedConditional { directive } => {
+                write!(f, "Unterminated '#{directive}'")
+            }
+            PreprocessErrorKind::UnmatchedConditional { directive } => {
+                write!(f, "'#{directive}' without '#if'")
+            }
+            PreprocessErrorKind::ElseAfterElse { directive } => {
+                write!(f, "'#{directive}' after '#else'")
+            }
+            PreprocessErrorKind::ErrorDirective { message } => write!(f, "#error {message}"),
+            PreprocessErrorKind::MissingExpression => {
+                write!(f, "Expected value in preprocessor expression")
+            }
+            PreprocessErrorKind::InvalidExpressionToken { token } => {
+                write!(
+                    f,
+                    "Token '{token}' is not valid in preprocessor expressions"
+                )
+            }
+            PreprocessErrorKind::ExpectedToken { expected } => write!(f, "Expected '{expected}'"),
+            PreprocessErrorKind::InvalidLineDirective => {
+                write!(
+                    f,
+                    "#line expects a line number and an optional \"FILENAME\""
+                )
+            }
+            PreprocessErrorKind::DivisionByZero => {
+                write!(f, "Division by zero in preprocessor expression")
+            }
+        }
+    }
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Error for PreprocessError {}
+
+pub type PreprocessResult<T> = Result<T, PreprocessError>;
@@ -0,0 +1,303 @@
+//! Evaluation of the integer constant expressions in `#if` and `#elif`
+//!
+//! Every value is either an `intmax_t` or a `uintmax_t`, which are 64 bits on x86-64. The usual
+//! arithmetic conversions still apply, so a single unsigned operand makes the whole operation
+//! unsigned.
+
+use std::cmp::Ordering;
+
+use lex::{Lexer, TokenType, token::IntSuffix};
+
+use crate::{PreprocessError, PreprocessErrorKind, PreprocessResult, Preprocessor, token::PpToken};
+
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    /// The bits of the value, which are reinterpreted as unsigned when `unsigned` is set
+    bits: i64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(value: i64) -> Value {
+        Value {
+            bits: value,
+            unsigned: false,
+        }
+    }
+
+    fn is_true(self) -> bool {
+        self.bits != 0
+    }
+}
+
+/// Binding power of binary operators, higher binds tighter
+fn precedence(ttype: TokenType) -> Option<u8> {
+    let precedence = match ttype {
+        TokenType::LogicalOr => 1,
+        TokenType::LogicalAnd => 2,
+        TokenType::Pipe => 3,
+        TokenType::Caret => 4,
+        TokenType::Ampersand => 5,
+        TokenType::EqualEqual | TokenType::NotEqual => 6,
+        TokenType::Less | TokenType::Greater | TokenType::LessEqual | TokenType::GreaterEqual => 7,
+        TokenType::ShiftLeft | TokenType::ShiftRight => 8,
+        TokenType::Plus | TokenType::Hyphen => 9,
+        TokenType::Asterisk | TokenType::FrontSlash | TokenType::Percent => 10,
+        _ => return None,
+    };
+
+    Some(precedence)
+}
+
+struct Evaluator<'a> {
+    preprocessor: &'a Preprocessor<'a>,
+    /// The directive being evaluated, errors at the end of the line point to it
+    directive: &'a PpToken,
+    tokens: &'a [PpToken],
+    position: usize,
+    /// How many enclosing operands are never evaluated, like the right side of `0 && x`. Errors
+    /// like division by zero are only reported for evaluated operands.
+    unevaluated: usize,
+}
+
+impl Preprocessor<'_> {
+    /// Evaluates the already macro-expanded condition of a `#if` or `#elif`
+    pub(crate) fn evaluate(
+        &self,
+        directive: &PpToken,
+        tokens: &[PpToken],
+    ) -> PreprocessResult<i64> {
+        let mut evaluator = Evaluator {
+            preprocessor: self,
+            directive,
+            tokens,
+            position: 0,
+            unevaluated: 0,
+        };
+
+        let value = evaluator.conditional()?;
+
+        match evaluator.peek() {
+            Some(token) => Err(evaluator.invalid_token(token)),
+            None => Ok(value.bits),
+        }
+    }
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&'a PpToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a PpToken> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, kind: PreprocessErrorKind, token: Option<&PpToken>) -> PreprocessError {
+        self.preprocessor
+            .error(kind, token.unwrap_or(self.directive))
+    }
+
+    fn invalid_token(&self, token: &PpToken) -> PreprocessError {
+        self.error(
+            PreprocessErrorKind::InvalidExpressionToken {
+                token: token.text.clone(),
+            },
+            Some(token),
+        )
+    }
+
+    fn expect(&mut self, ttype: TokenType, expected: char) -> PreprocessResult<()> {
+        match self.next() {
+            Some(token) if token.ttype == ttype => Ok(()),
+            token => Err(self.error(PreprocessErrorKind::ExpectedToken { expected }, token)),
+        }
+    }
+
+    /// Parses an operand which is only evaluated if `evaluated` is true
+    fn operand<T>(
+        &mut self,
+        evaluated: bool,
+        parse: impl FnOnce(&mut Self) -> PreprocessResult<T>,
+    ) -> PreprocessResult<T> {
+        self.unevaluated += !evaluated as usize;
+        let result = parse(self);
+        self.unevaluated -= !evaluated as usize;
+        result
+    }
+
+    /// conditional-expression: binary-expression ('?' expression ':' conditional-expression)?
+    fn conditional(&mut self) -> PreprocessResult<Value> {
+        let condition = self.binary(0)?;
+
+        if self.peek().map(|t| t.ttype) != Some(TokenType::Question) {
+            return Ok(condition);
+        }
+        self.next();
+
+        let then = self.operand(condition.is_true(), Self::conditional)?;
+        self.expect(TokenType::Colon, ':')?;
+        let otherwise = self.operand(!condition.is_true(), Self::conditional)?;
+
+        let value = if condition.is_true() { then } else { otherwise };
+
+        Ok(Value {
+            unsigned: then.unsigned || otherwise.unsigned,
+            ..value
+        })
+    }
+
+    /// Parses binary operators which bind tighter than `min_precedence` by precedence climbing
+    fn binary(&mut self, min_precedence: u8) -> PreprocessResult<Value> {
+        let mut left = self.unary()?;
+
+        while let Some(operator) = self.peek()
+            && let Some(precedence) = precedence(operator.ttype)
+            && precedence > min_precedence
+        {
+            self.next();
+
+            // Logical operators short-circuit, so their right side may not be evaluated
+            let evaluated = match operator.ttype {
+                TokenType::LogicalAnd => left.is_true(),
+                TokenType::LogicalOr => !left.is_true(),
+                _ => true,
+            };
+            let right = self.operand(evaluated, |this| this.binary(precedence))?;
+
+            left = self.apply(operator, left, right)?;
+        }
+
+        Ok(left)
+    }
+
+    fn apply(&self, operator: &PpToken, left: Value, right: Value) -> PreprocessResult<Value> {
+        let unsigned = left.unsigned || right.unsigned;
+        let (l, r) = (left.bits, right.bits);
+
+        let compare = |matches: fn(Ordering) -> bool| {
+            let ordering = if unsigned {
+                (l as u64).cmp(&(r as u64))
+            } else {
+                l.cmp(&r)
+            };
+            Value::signed(matches(ordering) as i64)
+        };
+
+        let value = match operator.ttype {
+            TokenType::LogicalOr => Value::signed((left.is_true() || right.is_true()) as i64),
+            TokenType::LogicalAnd => Value::signed((left.is_true() && right.is_true()) as i64),
+            TokenType::EqualEqual => Value::signed((l == r) as i64),
+            TokenType::NotEqual => Value::signed((l != r) as i64),
+            TokenType::Less => compare(|o| o.is_lt()),
+            TokenType::Greater => compare(|o| o.is_gt()),
+            TokenType::LessEqual => compare(|o| o.is_le()),
+            TokenType::GreaterEqual => compare(|o| o.is_ge()),
+            // Shifts have the type of their left operand
+            TokenType::ShiftLeft => Value {
+                bits: l.wrapping_shl(r as u32),
+                ..left
+            },
+            TokenType::ShiftRight if left.unsigned => Value {
+                bits: (l as u64).wrapping_shr(r as u32) as i64,
+                ..left
+            },
+            TokenType::ShiftRight => Value {
+                bits: l.wrapping_shr(r as u32),
+                ..left
+            },
+            ttype => {
+                let bits = match ttype {
+                    TokenType::Pipe => l | r,
+                    TokenType::Caret => l ^ r,
+                    TokenType::Ampersand => l & r,
+                    TokenType::Plus => l.wrapping_add(r),
+                    TokenType::Hyphen => l.wrapping_sub(r),
+                    TokenType::Asterisk => l.wrapping_mul(r),
+                    TokenType::FrontSlash | TokenType::Percent if r == 0 => {
+                        if self.unevaluated == 0 {
+                            return Err(
+                                self.error(PreprocessErrorKind::DivisionByZero, Some(operator))
+                            );
+                        }
+                        0
+                    }
+                    TokenType::FrontSlash if unsigned => ((l as u64) / (r as u64)) as i64,
+                    TokenType::FrontSlash => l.wrapping_div(r),
+                    TokenType::Percent if unsigned => ((l as u64) % (r as u64)) as i64,
+                    TokenType::Percent => l.wrapping_rem(r),
+                    _ => unreachable!("Not a binary operator: {ttype}"),
+                };
+
+                Value { bits, unsigned }
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// unary-expression: ('+' | '-' | '~' | '!') unary-expression | primary-expression
+    fn unary(&mut self) -> PreprocessResult<Value> {
+        let Some(token) = self.next() else {
+            return Err(self.error(PreprocessErrorKind::MissingExpression, None));
+        };
+
+        let value = match token.ttype {
+            TokenType::Plus => self.unary()?,
+            TokenType::Hyphen => {
+                let value = self.unary()?;
+                Value {
+                    bits: value.bits.wrapping_neg(),
+                    ..value
+                }
+            }
+            TokenType::Tilde => {
+                let value = self.unary()?;
+                Value {
+                    bits: !value.bits,
+                    ..value
+                }
+            }
+            TokenType::Bang => Value::signed(!self.unary()?.is_true() as i64),
+            TokenType::OpenParen => {
+                let value = self.conditional()?;
+                self.expect(TokenType::CloseParen, ')')?;
+                value
+            }
+            TokenType::IntConstant { suffix, .. } => {
+                let value = constant(token, |tokens| tokens.int_value(0))
+                    .ok_or_else(|| self.invalid_token(token))?;
+
+                // Constants which don't fit in an intmax_t are unsigned
+                Value {
+                    bits: value as i64,
+                    unsigned: value > i64::MAX as u64
+                        || matches!(
+                            suffix,
+                            IntSuffix::Unsigned
+                                | IntSuffix::UnsignedLong
+                                | IntSuffix::UnsignedLongLong
+                        ),
+                }
+            }
+            TokenType::CharLiteral => {
+                let value = constant(token, |tokens| tokens.char_value(0))
+                    .ok_or_else(|| self.invalid_token(token))?;
+                Value::signed(value as i64)
+            }
+            // Identifiers left after expanding macros are replaced with 0, keywords included since
+            // they're just identifiers to the preprocessor
+            TokenType::Ident | TokenType::Keyword(_) => Value::signed(0),
+            _ => return Err(self.invalid_token(token)),
+        };
+
+        Ok(value)
+    }
+}
+
+/// Lexes a constant on its own to get its value, `None` if it's malformed
+fn constant<T>(token: &PpToken, value: impl FnOnce(&lex::TokenizedOutput) -> T) -> Option<T> {
+    Lexer::lex(&token.text).ok().map(|tokens| value(&tokens))
+}
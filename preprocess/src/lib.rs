@@ -0,0 +1,970 @@
+mod conditional;
+pub mod error;
+mod expr;
+mod macros;
+mod output;
+pub mod token;
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+pub use error::*;
+use lex::{
+    Lexer, TokenType,
+    source_map::{FileId, SourceMap},
+    token::Span,
+};
+pub use output::PreprocessedOutput;
+pub use token::{Location, PpToken};
+use tracing::{Level, debug, span};
+
+use crate::{conditional::Conditional, macros::Macro};
+
+/// How many nested `#include`s are allowed before giving up, this is what stops a file which
+/// includes itself from recursing forever
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// Expands macros and resolves directives, turning source files into a single stream of tokens
+/// ready to be parsed
+pub struct Preprocessor<'s> {
+    /// Where included files are loaded into, so every `Location` can be resolved through it
+    sources: &'s mut SourceMap,
+    include_paths: Vec<PathBuf>,
+    macros: HashMap<String, Macro>,
+    /// Files which had a `#pragma once`, and so must not be included again
+    included_once: HashSet<PathBuf>,
+    include_depth: usize,
+}
+
+/// A file being preprocessed
+struct SourceFile {
+    file: FileId,
+    /// Tokens yet to be preprocessed, in reverse order so they can be popped
+    input: Vec<PpToken>,
+    /// Conditional directives which haven't reached their `#endif` yet
+    conditionals: Vec<Conditional>,
+}
+
+impl<'s> Preprocessor<'s> {
+    /// Creates a preprocessor which searches `include_paths`, in order, for included files and
+    /// loads them into `sources`
+    pub fn new(sources: &'s mut SourceMap, include_paths: Vec<PathBuf>) -> Preprocessor<'s> {
+        Preprocessor {
+            sources,
+            include_paths,
+            macros: HashMap::new(),
+            included_once: HashSet::new(),
+            include_depth: 0,
+        }
+    }
+
+    /// Defines a macro as the `-D` flag does, `definition` being either "NAME" which defines it as
+    /// 1, or "NAME=value"
+    pub fn define_macro(&mut self, definition: &str) -> PreprocessResult<()> {
+        let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+        let file = self
+            .sources
+            .add_file("<command line>", format!("#define {name} {value}"));
+
+        self.preprocess_file(file, &mut Vec::new())
+    }
+
+    /// Undefines a macro as the `-U` flag does
+    pub fn undefine_macro(&mut self, name: &str) -> PreprocessResult<()> {
+        let file = self
+            .sources
+            .add_file("<command line>", format!("#undef {name}"));
+
+        self.preprocess_file(file, &mut Vec::new())
+    }
+
+    /// Preprocesses `file`, which must have been added to the preprocessor's `SourceMap`
+    pub fn preprocess(mut self, file: FileId) -> PreprocessResult<PreprocessedOutput> {
+        let _ = span!(Level::TRACE, "Preprocessing").entered();
+
+        let mut output = Vec::new();
+        self.preprocess_file(file, &mut output)?;
+
+        Ok(PreprocessedOutput::new(self.sources.clone(), file, output))
+    }
+
+    fn preprocess_file(&mut self, file: FileId, output: &mut Vec<PpToken>) -> PreprocessResult<()> {
+        debug!("Preprocessing {:?}", self.sources.path(file));
+
+        let mut input = tokenize(self.sources, file);
+        input.reverse();
+
+        let mut source = SourceFile {
+            file,
+            input,
+            conditionals: Vec::new(),
+        };
+
+        while let Some(token) = source.input.pop() {
+            if token.ttype == TokenType::Hash && token.at_line_start {
+                let line = directive_line(&mut source.input);
+                self.directive(&mut source, token, line, output)?;
+            } else if !self.expand_macro(&token, &mut source.input)? {
+                output.push(token);
+            }
+        }
+
+        // Conditionals can't span files, so they all must be closed by now
+        match source.conditionals.pop() {
+            Some(conditional) => Err(self.error(
+                PreprocessErrorKind::UnterminatedConditional {
+                    directive: conditional.directive,
+                },
+                &conditional.hash,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Handles the directive which starts at `hash`, `line` being the rest of its tokens
+    fn directive(
+        &mut self,
+        source: &mut SourceFile,
+        hash: PpToken,
+        line: Vec<PpToken>,
+        output: &mut Vec<PpToken>,
+    ) -> PreprocessResult<()> {
+        // A '#' on its own is the null directive, which does nothing
+        let Some((name, args)) = line.split_first() else {
+            return Ok(());
+        };
+
+        match name.text.as_str() {
+            "define" => self.define(name, args),
+            "undef" => {
+                let name = self.macro_name(name, args)?;
+                self.macros.remove(&name.text);
+                Ok(())
+            }
+            "include" => self.include(source.file, name, args, output),
+            "line" => {
+                let args = self.expand_all(args.to_vec())?;
+                self.line(source, &hash, name, &args)
+            }
+            // Linemarkers left by another preprocessor, `# 42 "file.c"`, are `#line` directives
+            // whose arguments aren't expanded
+            _ if matches!(name.ttype, TokenType::IntConstant { .. }) => {
+                self.line(source, &hash, name, &line)
+            }
+            "if" | "ifdef" | "ifndef" | "elif" | "else" | "endif" => {
+                self.conditional(source, hash, name, args)
+            }
+            "error" => Err(self.error(
+                PreprocessErrorKind::ErrorDirective {
+                    message: render_line(args),
+                },
+                &hash,
+            )),
+            "pragma" => {
+                if args.first().is_some_and(|arg| arg.text == "once") {
+                    let path = canonical_path(self.sources.path(source.file));
+                    self.included_once.insert(path);
+                }
+                // Unknown pragmas must be ignored
+                Ok(())
+            }
+            _ => Err(self.error(
+                PreprocessErrorKind::UnknownDirective {
+                    name: name.text.clone(),
+                },
+                if name.is_ident() { name } else { &hash },
+            )),
+        }
+    }
+
+    /// Returns the name of the macro a directive refers to, which must be its first token
+    fn macro_name<'a>(
+        &self,
+        directive: &PpToken,
+        args: &'a [PpToken],
+    ) -> PreprocessResult<&'a PpToken> {
+        match args.first() {
+            Some(name) if name.is_ident() => Ok(name),
+            Some(token) => Err(self.error(PreprocessErrorKind::MissingMacroName, token)),
+            None => Err(self.error(PreprocessErrorKind::MissingMacroName, directive)),
+        }
+    }
+
+    fn include(
+        &mut self,
+        file: FileId,
+        directive: &PpToken,
+        args: &[PpToken],
+        output: &mut Vec<PpToken>,
+    ) -> PreprocessResult<()> {
+        let (name, quoted) = match include_name(args) {
+            Some(include) => include,
+            // Computed include, the macros have to expand to one of the other two forms
+            None => {
+                let expanded = self.expand_all(args.to_vec())?;
+                include_name(&expanded)
+                    .ok_or_else(|| self.error(PreprocessErrorKind::InvalidInclude, directive))?
+            }
+        };
+
+        if self.include_depth >= MAX_INCLUDE_DEPTH {
+            return Err(self.error(PreprocessErrorKind::IncludeTooDeep, directive));
+        }
+
+        // Quoted includes are first searched relative to the file which includes them
+        let current_dir = self
+            .sources
+            .path(file)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let search_dirs = quoted
+            .then_some(&current_dir)
+            .into_iter()
+            .chain(&self.include_paths);
+
+        let Some(path) = search_dirs
+            .map(|dir| dir.join(&name))
+            .find(|path| path.is_file())
+        else {
+            return Err(self.error(PreprocessErrorKind::IncludeNotFound { name }, directive));
+        };
+
+        if self.included_once.contains(&canonical_path(&path)) {
+            debug!("Skipping {path:?} since it has #pragma once");
+            return Ok(());
+        }
+
+        let included = self.sources.load(&path).map_err(|e| {
+            self.error(
+                PreprocessErrorKind::Io {
+                    message: format!("Couldn't read {}: {e}", path.display()),
+                },
+                directive,
+            )
+        })?;
+
+        self.include_depth += 1;
+        self.preprocess_file(included, output)?;
+        self.include_depth -= 1;
+
+        Ok(())
+    }
+
+    /// Handles a `#line` directive, `args` being the line number and optional file name. Every
+    /// token left in the file is moved to the new line and file.
+    fn line(
+        &mut self,
+        source: &mut SourceFile,
+        hash: &PpToken,
+        directive: &PpToken,
+        args: &[PpToken],
+    ) -> PreprocessResult<()> {
+        let invalid = || self.error(PreprocessErrorKind::InvalidLineDirective, directive);
+
+        let (line, file) = match args {
+            [line] => (line, None),
+            [line, file, ..] if file.ttype == TokenType::StringLiteral => (line, Some(file)),
+            _ => return Err(invalid()),
+        };
+
+        let line: usize = match line.ttype {
+            TokenType::IntConstant { .. } if line.text.bytes().all(|c| c.is_ascii_digit()) => {
+                line.text.parse().map_err(|_| invalid())?
+            }
+            _ => return Err(invalid()),
+        };
+
+        // The renamed file keeps the contents of the one being preprocessed, so spans stay valid
+        let file = match file {
+            Some(file) => self
+                .sources
+                .rename(source.file, &file.text[1..file.text.len() - 1]),
+            None => hash.location.file,
+        };
+
+        // The line after the directive becomes `line`, and every following one moves with it
+        let next_line = hash.location.position.line + 1;
+
+        for token in &mut source.input {
+            let position = &mut token.location.position;
+            position.line = (position.line + line).saturating_sub(next_line + 1);
+            token.location.file = file;
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, kind: PreprocessErrorKind, token: &PpToken) -> PreprocessError {
+        PreprocessError {
+            kind,
+            location: token.presumed_location(),
+        }
+    }
+}
+
+/// Lexes `source` into preprocessing tokens
+///
+/// Lex errors are ignored here, they're reported when the preprocessed output gets lexed again.
+/// Reporting them now would also mean rejecting code which is never compiled.
+fn tokenize(sources: &SourceMap, file: FileId) -> Vec<PpToken> {
+    let lexed = Lexer::new(sources, file).keep_line_markers().tokenize();
+    let mut previous = None;
+
+    lexed
+        .tokens()
+        .iter()
+        .map(|token| {
+            let token_source = lexed.token_source(token.handle);
+            let (leading_space, at_line_start) = match previous {
+                // Lines joined by line splices are a single line as far as directives go, so only
+                // newlines left after removing them count
+                Some(end) => {
+                    let between = Span {
+                        start: end,
+                        end: token_source.span.start,
+                    };
+                    let newline = sources.spelling(file, between).contains('\n');
+
+                    (end != token_source.span.start, newline)
+                }
+                None => (false, true),
+            };
+            previous = Some(token_source.span.end);
+
+            PpToken {
+                ttype: token.ttype,
+                text: lexed.token_text(token.handle).to_string(),
+                leading_space,
+                at_line_start,
+                location: Location {
+                    file,
+                    span: token_source.span,
+                    position: lexed.position(token_source.span.start),
+                },
+                expanded_from: None,
+                hide_set: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Pops the tokens which belong to the same line as the directive which was just popped
+fn directive_line(input: &mut Vec<PpToken>) -> Vec<PpToken> {
+    let mut line = Vec::new();
+
+    while let Some(token) = input.pop_if(|token| !token.at_line_start) {
+        line.push(token);
+    }
+
+    line
+}
+
+/// Name of an included file and whether it was quoted, or `None` if the tokens aren't in either the
+/// `"file"` or `<file>` forms
+fn include_name(tokens: &[PpToken]) -> Option<(String, bool)> {
+    match tokens {
+        [name] if name.ttype == TokenType::StringLiteral => {
+            Some((name.text[1..name.text.len() - 1].to_string(), true))
+        }
+        [open, rest @ ..] if open.ttype == TokenType::Less => {
+            let close = rest.iter().position(|t| t.ttype == TokenType::Greater)?;
+
+            Some((render_line(&rest[..close]), false))
+        }
+        _ => None,
+    }
+}
+
+/// Joins the text of the tokens in a line, with a space wherever there was whitespace
+fn render_line(tokens: &[PpToken]) -> String {
+    let mut text = String::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && token.leading_space {
+            text.push(' ');
+        }
+        text.push_str(&token.text);
+    }
+
+    text
+}
+
+/// Wraps `text` in double quotes, escaping it so it's a valid string literal
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+
+    quoted.push('"');
+    for c in text.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Path used to tell whether two paths are the same file, falling back to the path itself if it
+/// can't be resolved
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use lex::{TokenType, line::Position, source_map::SourceMap};
+
+    use crate::{PreprocessErrorKind, PreprocessResult, PreprocessedOutput, Preprocessor};
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+    }
+
+    fn preprocess(source: &str) -> PreprocessResult<PreprocessedOutput> {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file(fixtures().join("main.c"), source);
+
+        Preprocessor::new(&mut sources, vec![fixtures().join("include")]).preprocess(main)
+    }
+
+    /// Preprocesses `source`, returning the text of the resulting tokens separated by spaces
+    fn expand(source: &str) -> String {
+        preprocess(source)
+            .unwrap()
+            .tokens()
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn error(source: &str) -> PreprocessErrorKind {
+        preprocess(source).unwrap_err().kind
+    }
+
+    #[test]
+    fn no_directives() {
+        assert_eq!(
+            expand("int main(void) {\n    return 0;\n}"),
+            "int main ( void ) { return 0 ; }"
+        );
+    }
+
+    #[test]
+    fn object_like() {
+        assert_eq!(expand("#define ZERO 0\nreturn ZERO;"), "return 0 ;");
+        assert_eq!(expand("#define EMPTY\nreturn EMPTY 1;"), "return 1 ;");
+        assert_eq!(
+            expand("#define A B + 1\n#define B 2\nA * A"),
+            "2 + 1 * 2 + 1"
+        );
+        assert_eq!(expand("#define int long\nint x;"), "long x ;");
+    }
+
+    #[test]
+    fn line_splices() {
+        assert_eq!(
+            expand("#define ONE \\\n 1\n#def\\\nine TWO 2\nONE TWO"),
+            "1 2"
+        );
+        assert_eq!(expand("??=define ARR(x) x??(0??)\nARR(a)"), "a [ 0 ]");
+        assert_eq!(expand("%:define CAT(a, b) a %:%: b\nCAT(x, y)"), "xy");
+    }
+
+    #[test]
+    fn undef() {
+        assert_eq!(expand("#define X 1\n#undef X\nX"), "X");
+    }
+
+    #[test]
+    fn self_reference() {
+        assert_eq!(expand("#define foo foo\nfoo"), "foo");
+        assert_eq!(expand("#define foo a foo b\nfoo"), "a foo b");
+        assert_eq!(
+            expand("#define x (4 + y)\n#define y (2 * x)\nx y"),
+            "( 4 + ( 2 * x ) ) ( 2 * ( 4 + y ) )"
+        );
+    }
+
+    #[test]
+    fn function_like() {
+        assert_eq!(expand("#define F(a, b) a - b\nF(1, 2)"), "1 - 2");
+        assert_eq!(expand("#define F() 1\nF()"), "1");
+        assert_eq!(expand("#define F(a) a\nF()"), "");
+        assert_eq!(
+            expand("#define F(a, b) a - b\nF((1, 2), f(3, 4))"),
+            "( 1 , 2 ) - f ( 3 , 4 )"
+        );
+        // Arguments can span several lines
+        assert_eq!(expand("#define F(a, b) a - b\nF(1,\n2\n)"), "1 - 2");
+        // Without parens it's just an identifier
+        assert_eq!(expand("#define F(a) a\nF + 1"), "F + 1");
+        // A space before the paren makes it object-like
+        assert_eq!(expand("#define F (a) a\nF"), "( a ) a");
+    }
+
+    #[test]
+    fn nested_calls() {
+        assert_eq!(
+            expand("#define SQ(x) ((x) * (x))\nSQ(SQ(2))"),
+            "( ( ( ( 2 ) * ( 2 ) ) ) * ( ( ( 2 ) * ( 2 ) ) ) )"
+        );
+        assert_eq!(
+            expand("#define f(a) a*g\n#define g(a) f(a)\nf(2)(9)"),
+            "2 * 9 * g"
+        );
+    }
+
+    #[test]
+    fn stringize() {
+        assert_eq!(expand("#define S(x) #x\nS(a  +   b)"), "\"a + b\"");
+        assert_eq!(expand("#define S(x) #x\nS()"), "\"\"");
+        assert_eq!(
+            expand("#define S(x) #x\nS(\"a\\n\" 'b')"),
+            r#""\"a\\n\" 'b'""#
+        );
+    }
+
+    #[test]
+    fn paste() {
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(x, y)"), "xy");
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(1, 2)"), "12");
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(+, =)"), "+=");
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(, y)"), "y");
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(x, )"), "x");
+        assert_eq!(expand("#define CAT(a, b) a ## b\nCAT(,)"), "");
+        // Arguments next to '##' aren't expanded
+        assert_eq!(
+            expand("#define X 1\n#define CAT(a, b) a ## b\nCAT(X, X)"),
+            "XX"
+        );
+        assert_eq!(expand("#define VAR(n) x ## n ## _\nVAR(1)"), "x1_");
+
+        let output = preprocess("#define CAT(a, b) a ## b\nCAT(x, 1)").unwrap();
+        assert_eq!(output.tokens()[0].ttype, TokenType::Ident);
+    }
+
+    #[test]
+    fn variadic() {
+        assert_eq!(
+            expand("#define F(fmt, ...) f(fmt, __VA_ARGS__)\nF(\"%d %d\", 1, 2)"),
+            "f ( \"%d %d\" , 1 , 2 )"
+        );
+        assert_eq!(expand("#define F(...) #__VA_ARGS__\nF(a, b)"), "\"a, b\"");
+        assert_eq!(expand("#define F(a, ...) a\nF(1)"), "1");
+    }
+
+    #[test]
+    fn builtins() {
+        assert_eq!(expand("__LINE__\n\n__LINE__"), "1 3");
+        assert_eq!(
+            expand("#define L __LINE__\nL\nL"),
+            "2 3",
+            "__LINE__ is the line of the outermost invocation"
+        );
+        assert_eq!(
+            expand("__FILE__"),
+            format!("\"{}\"", fixtures().join("main.c").display())
+        );
+    }
+
+    #[test]
+    fn include() {
+        assert_eq!(
+            expand("#include \"local.h\"\nint y = LOCAL;"),
+            "int local = 1 ; int y = 1 ;"
+        );
+        assert_eq!(expand("#include <system.h>\nSYSTEM"), "2");
+        assert_eq!(
+            expand("#define HEADER <system.h>\n#include HEADER\nSYSTEM"),
+            "2"
+        );
+        // Quoted includes also search the include paths
+        assert_eq!(expand("#include \"system.h\"\nSYSTEM"), "2");
+    }
+
+    #[test]
+    fn include_locations() {
+        let output = preprocess("#include \"local.h\"\n  x").unwrap();
+        let tokens = output.tokens();
+
+        assert_eq!(
+            output.path(tokens[0].location.file),
+            fixtures().join("local.h")
+        );
+        assert_eq!(
+            output.path(tokens[5].location.file),
+            fixtures().join("main.c")
+        );
+        assert_eq!(tokens[5].location.position, Position { line: 1, column: 2 });
+    }
+
+    #[test]
+    fn expansion_locations() {
+        let output = preprocess("#define ONE 1\nint x =  ONE;").unwrap();
+        let one = &output.tokens()[3];
+
+        assert_eq!(
+            one.location.position,
+            Position {
+                line: 0,
+                column: 12
+            }
+        );
+        assert_eq!(
+            one.presumed_location().position,
+            Position { line: 1, column: 9 }
+        );
+    }
+
+    #[test]
+    fn directives() {
+        assert_eq!(expand("#\n# pragma once\n1"), "1");
+        assert_eq!(
+            error("#foo"),
+            PreprocessErrorKind::UnknownDirective {
+                name: "foo".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(expand("#if 1\na\n#else\nb\n#endif"), "a");
+        assert_eq!(expand("#if 0\na\n#else\nb\n#endif"), "b");
+        assert_eq!(expand("#if 0\na\n#elif 1\nb\n#elif 1\nc\n#endif"), "b");
+        assert_eq!(expand("#if 0\na\n#elif 0\nb\n#endif\nc"), "c");
+        assert_eq!(expand("#define X\n#ifdef X\na\n#endif"), "a");
+        assert_eq!(expand("#ifdef X\na\n#endif"), "");
+        assert_eq!(expand("#ifndef X\na\n#else\nb\n#endif"), "a");
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        assert_eq!(
+            expand("#if 0\n#if 1\na\n#else\nb\n#endif\nc\n#else\nd\n#endif"),
+            "d"
+        );
+        assert_eq!(
+            expand("#if 1\n#if 0\na\n#else\nb\n#endif\nc\n#else\nd\n#endif"),
+            "b c"
+        );
+        // Skipped groups aren't preprocessed at all, not even their unknown directives
+        assert_eq!(expand("#if 0\n#foo\n#if 1 / 0\n#endif\n#endif"), "");
+    }
+
+    #[test]
+    fn if_expressions() {
+        let eval = |condition: &str| expand(&format!("#if {condition}\n1\n#else\n0\n#endif"));
+
+        assert_eq!(eval("1 + 2 * 3 == 7"), "1");
+        assert_eq!(eval("(1 + 2) * 3 == 7"), "0");
+        assert_eq!(eval("10 - 2 - 3 == 5"), "1");
+        assert_eq!(eval("-1 < 0"), "1");
+        assert_eq!(eval("~0 == -1 && !0"), "1");
+        assert_eq!(eval("1 << 4 | 3 ^ 1 & 1"), "1");
+        assert_eq!(eval("7 % 4 / 3"), "1");
+        assert_eq!(eval("0 ? 0 : 2 > 1"), "1");
+        assert_eq!(eval("'a' == 97 && 0x10 == 020"), "1");
+        // Undefined identifiers and keywords are 0
+        assert_eq!(eval("UNDEFINED || int"), "0");
+        // intmax_t is 64 bits
+        assert_eq!(eval("9223372036854775807 + 1 < 0"), "1");
+        // A single unsigned operand makes the comparison unsigned
+        assert_eq!(eval("-1 < 0u"), "0");
+        assert_eq!(eval("18446744073709551615 == -1"), "1");
+        assert_eq!(eval("-1 >> 63 == -1 && -1u >> 63 == 1"), "1");
+        // Unevaluated operands can't fail
+        assert_eq!(eval("0 && 1 / 0"), "0");
+        assert_eq!(eval("1 || 1 % 0"), "1");
+        assert_eq!(eval("1 ? 1 : 1 / 0"), "1");
+    }
+
+    #[test]
+    fn defined() {
+        let source = "#define X 0\n#if defined X && defined(X) && !defined Y\n1\n#endif";
+        assert_eq!(expand(source), "1");
+
+        assert_eq!(
+            expand("#define X Y\n#define Y 1\n#if X\nx\n#endif"),
+            "x",
+            "Conditions are macro expanded"
+        );
+        assert_eq!(
+            error("#if defined(X\n#endif"),
+            PreprocessErrorKind::ExpectedToken { expected: ')' }
+        );
+        assert_eq!(
+            error("#if defined 1\n#endif"),
+            PreprocessErrorKind::MissingMacroName
+        );
+    }
+
+    #[test]
+    fn if_errors() {
+        assert_eq!(error("#if\n#endif"), PreprocessErrorKind::MissingExpression);
+        assert_eq!(
+            error("#if 1 +\n#endif"),
+            PreprocessErrorKind::MissingExpression
+        );
+        assert_eq!(
+            error("#if 1 / 0\n#endif"),
+            PreprocessErrorKind::DivisionByZero
+        );
+        assert_eq!(
+            error("#if (1\n#endif"),
+            PreprocessErrorKind::ExpectedToken { expected: ')' }
+        );
+        assert_eq!(
+            error("#if 1 ? 2\n#endif"),
+            PreprocessErrorKind::ExpectedToken { expected: ':' }
+        );
+        assert_eq!(
+            error("#if 1.0\n#endif"),
+            PreprocessErrorKind::InvalidExpressionToken {
+                token: "1.0".to_string()
+            }
+        );
+        assert_eq!(
+            error("#if 1 2\n#endif"),
+            PreprocessErrorKind::InvalidExpressionToken {
+                token: "2".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        let unterminated = preprocess("int x;\n  #ifdef X\n#if 1\n#endif").unwrap_err();
+        assert_eq!(
+            unterminated.kind,
+            PreprocessErrorKind::UnterminatedConditional {
+                directive: "ifdef".to_string()
+            }
+        );
+        assert_eq!(
+            unterminated.location.position,
+            Position { line: 1, column: 2 }
+        );
+
+        let unmatched = preprocess("#if 1\n#endif\n#endif").unwrap_err();
+        assert_eq!(
+            unmatched.kind,
+            PreprocessErrorKind::UnmatchedConditional {
+                directive: "endif".to_string()
+            }
+        );
+        assert_eq!(unmatched.location.position, Position { line: 2, column: 0 });
+
+        assert_eq!(
+            error("#else"),
+            PreprocessErrorKind::UnmatchedConditional {
+                directive: "else".to_string()
+            }
+        );
+        assert_eq!(
+            error("#if 1\n#else\n#elif 1\n#endif"),
+            PreprocessErrorKind::ElseAfterElse {
+                directive: "elif".to_string()
+            }
+        );
+        assert_eq!(
+            error("#if 0\n#else\n#else\n#endif"),
+            PreprocessErrorKind::ElseAfterElse {
+                directive: "else".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn error_directive() {
+        assert_eq!(
+            error("#error Something  went \"wrong\""),
+            PreprocessErrorKind::ErrorDirective {
+                message: "Something went \"wrong\"".to_string()
+            }
+        );
+        assert_eq!(expand("#if 0\n#error Unreachable\n#endif"), "");
+    }
+
+    #[test]
+    fn pragma_once() {
+        assert_eq!(
+            expand("#include \"once.h\"\n#include \"once.h\"\n#include \"./once.h\""),
+            "int once ;"
+        );
+    }
+
+    #[test]
+    fn command_line_macros() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file("main.c", "A B F(3) C");
+
+        let mut preprocessor = Preprocessor::new(&mut sources, Vec::new());
+        preprocessor.define_macro("A").unwrap();
+        preprocessor.define_macro("B=2").unwrap();
+        preprocessor.define_macro("F(x)=x + 1").unwrap();
+        preprocessor.define_macro("C").unwrap();
+        preprocessor.undefine_macro("C").unwrap();
+
+        let output = preprocessor.preprocess(main).unwrap();
+
+        assert_eq!(output.to_string(), "1 2 3 + 1 C");
+
+        let error = Preprocessor::new(&mut sources, Vec::new())
+            .define_macro("1=2")
+            .unwrap_err();
+
+        assert_eq!(error.kind, PreprocessErrorKind::MissingMacroName);
+        assert_eq!(
+            sources.path(error.location.file),
+            Path::new("<command line>")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("#define 1"), PreprocessErrorKind::MissingMacroName);
+        assert_eq!(
+            error("#define F(a"),
+            PreprocessErrorKind::InvalidMacroParams
+        );
+        assert_eq!(
+            error("#define F(a, a) a"),
+            PreprocessErrorKind::DuplicateMacroParam {
+                name: "a".to_string()
+            }
+        );
+        assert_eq!(
+            error("#define F(a) #b"),
+            PreprocessErrorKind::HashWithoutParam
+        );
+        assert_eq!(error("#define F ## a"), PreprocessErrorKind::PasteAtEdge);
+        assert_eq!(
+            error("#define X 1\n#define X 2"),
+            PreprocessErrorKind::MacroRedefined {
+                name: "X".to_string()
+            }
+        );
+        assert_eq!(
+            error("#define CAT(a, b) a ## b\nCAT(+, /)"),
+            PreprocessErrorKind::InvalidPaste {
+                left: "+".to_string(),
+                right: "/".to_string()
+            }
+        );
+        assert_eq!(
+            error("#define F(a) a\nF(1"),
+            PreprocessErrorKind::UnterminatedMacroCall {
+                name: "F".to_string()
+            }
+        );
+        assert_eq!(
+            error("#define F(a) a\nF(1, 2)"),
+            PreprocessErrorKind::WrongArgCount {
+                name: "F".to_string(),
+                expected: 1,
+                found: 2
+            }
+        );
+        assert_eq!(error("#include x"), PreprocessErrorKind::InvalidInclude);
+        assert_eq!(
+            error("#include \"missing.h\""),
+            PreprocessErrorKind::IncludeNotFound {
+                name: "missing.h".to_string()
+            }
+        );
+        assert_eq!(
+            error("#include \"recursive.h\""),
+            PreprocessErrorKind::IncludeTooDeep
+        );
+
+        // Identical redefinitions are fine
+        assert_eq!(expand("#define X  1 +  2\n#define X 1 + 2\nX"), "1 + 2");
+    }
+
+    #[test]
+    fn error_location() {
+        let mut sources = SourceMap::new();
+        let main = sources.add_file("main.c", "int x;\n  #define 1");
+
+        let error = Preprocessor::new(&mut sources, Vec::new())
+            .preprocess(main)
+            .unwrap_err();
+
+        assert_eq!(error.location.file, main);
+        assert_eq!(
+            error.location.position,
+            Position {
+                line: 1,
+                column: 10
+            }
+        );
+    }
+
+    #[test]
+    fn render() {
+        let output = preprocess(
+            "#include \"local.h\"\n#define ADD(a, b) a+b\n\nint main(void) {\n    return ADD(1, -LOCAL);  /* comment */ 2;\n}",
+        )
+        .unwrap();
+
+        // Linemarkers have absolute paths, which depend on where the repo is
+        let fixtures = fixtures().display().to_string();
+        insta::assert_snapshot!(output.to_string().replace(&fixtures, "fixtures"));
+    }
+
+    #[test]
+    fn line_directive() {
+        assert_eq!(expand("#line 10\n__LINE__\n__LINE__"), "10 11");
+        assert_eq!(
+            expand("#define N 42\n#define F \"other.c\"\n#line N F\n__FILE__ __LINE__"),
+            "\"other.c\" 42"
+        );
+        // Linemarkers from other preprocessors work the same, but aren't expanded
+        assert_eq!(expand("# 7 \"other.c\" 1\n__LINE__"), "7");
+
+        let output = preprocess("int x;\n#line 100 \"other.c\"\n  y;").unwrap();
+        let y = &output.tokens()[3];
+        assert_eq!(output.path(y.location.file), Path::new("other.c"));
+        // The renamed file has the same contents, so spans still point at the right text
+        assert_eq!(output.sources().text(y.location.file, y.location.span), "y");
+        assert_eq!(
+            y.location.position,
+            Position {
+                line: 99,
+                column: 2
+            }
+        );
+
+        assert_eq!(error("#line"), PreprocessErrorKind::InvalidLineDirective);
+        assert_eq!(error("#line x"), PreprocessErrorKind::InvalidLineDirective);
+        assert_eq!(
+            error("#line 0x10"),
+            PreprocessErrorKind::InvalidLineDirective
+        );
+        assert_eq!(
+            error("#line 1 x"),
+            PreprocessErrorKind::InvalidLineDirective
+        );
+    }
+
+    #[test]
+    fn render_line_markers() {
+        let output = preprocess("a\nb\n#line 1\nc\n#line 5 \"other.c\"\nd\ne").unwrap();
+
+        assert_eq!(
+            output.to_string(),
+            format!(
+                "a\nb\n# 1 {:?}\nc\n# 5 \"other.c\"\nd\ne",
+                fixtures().join("main.c")
+            )
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Macro definitions and expansion
+//!
+//! Expansion follows the hide set algorithm described by Dave Prosser: every token remembers which
+//! macros it was produced by, and a macro is never expanded from a token which it produced. This
+//! is what lets `#define foo foo` terminate while still expanding everything the standard requires.
+
+use lex::{
+    Lexer, TokenType,
+    token::{IntSuffix, Radix},
+};
+
+use crate::{
+    PreprocessErrorKind, PreprocessResult, Preprocessor, quote, render_line, token::PpToken,
+};
+
+/// The name which refers to the extra arguments of a variadic macro
+const VA_ARGS: &str = "__VA_ARGS__";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Macro {
+    /// `None` for object-like macros
+    params: Option<Vec<String>>,
+    /// Whether the last parameter is `...`, in which case `params` doesn't include it
+    variadic: bool,
+    body: Vec<PpToken>,
+}
+
+impl Macro {
+    /// Whether two definitions are the same, which is the only way a macro can be redefined.
+    /// Definitions are compared token by token, only caring about whether there's whitespace
+    /// between them and not how much.
+    fn same_definition(&self, other: &Macro) -> bool {
+        self.params == other.params
+            && self.variadic == other.variadic
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(&other.body)
+                .enumerate()
+                .all(|(i, (a, b))| {
+                    a.text == b.text && (i == 0 || a.leading_space == b.leading_space)
+                })
+    }
+
+    /// Index of the parameter called `name`, `__VA_ARGS__` being right after the named ones
+    fn param(&self, name: &PpToken) -> Option<usize> {
+        let params = self.params.as_ref()?;
+
+        if !name.is_ident() {
+            None
+        } else if self.variadic && name.text == VA_ARGS {
+            Some(params.len())
+        } else {
+            params.iter().position(|param| *param == name.text)
+        }
+    }
+}
+
+impl Preprocessor<'_> {
+    /// Handles a `#define`, `args` being the tokens after "define"
+    pub(crate) fn define(&mut self, directive: &PpToken, args: &[PpToken]) -> PreprocessResult<()> {
+        let name = self.macro_name(directive, args)?;
+        let mut rest = &args[1..];
+
+        // Function-like macros need the paren right after the name, otherwise the paren is just
+        // the start of the body
+        let (params, variadic) = match rest.first() {
+            Some(paren) if paren.ttype == TokenType::OpenParen && !paren.leading_space => {
+                let (params, variadic, len) = self.macro_params(paren, &rest[1..])?;
+                rest = &rest[1 + len..];
+                (Some(params), variadic)
+            }
+            _ => (None, false),
+        };
+
+        let new = Macro {
+            params,
+            variadic,
+            body: rest.to_vec(),
+        };
+
+        if let Some(first) = new.body.first()
+            && first.ttype == TokenType::HashHash
+        {
+            return Err(self.error(PreprocessErrorKind::PasteAtEdge, first));
+        }
+        if let Some(last) = new.body.last()
+            && last.ttype == TokenType::HashHash
+        {
+            return Err(self.error(PreprocessErrorKind::PasteAtEdge, last));
+        }
+
+        if new.params.is_some() {
+            for (i, token) in new.body.iter().enumerate() {
+                if token.ttype == TokenType::Hash
+                    && new.body.get(i + 1).and_then(|t| new.param(t)).is_none()
+                {
+                    return Err(self.error(PreprocessErrorKind::HashWithoutParam, token));
+                }
+            }
+        }
+
+        if let Some(old) = self.macros.get(&name.text)
+            && !old.same_definition(&new)
+        {
+            return Err(self.error(
+                PreprocessErrorKind::MacroRedefined {
+                    name: name.text.clone(),
+                },
+                name,
+            ));
+        }
+
+        self.macros.insert(name.text.clone(), new);
+
+        Ok(())
+    }
+
+    /// Parses the parameters of a function-like macro, which come after `paren`
+    ///
+    /// Returns the named parameters, whether the macro is variadic and how many tokens were used,
+    /// including the closing paren.
+    fn macro_params(
+        &self,
+        paren: &PpToken,
+        tokens: &[PpToken],
+    ) -> PreprocessResult<(Vec<String>, bool, usize)> {
+        let invalid = |token: Option<&PpToken>| {
+            self.error(
+                PreprocessErrorKind::InvalidMacroParams,
+                token.unwrap_or(paren),
+            )
+        };
+
+        let mut params: Vec<String> = Vec::new();
+        let mut variadic = false;
+        let mut i = 0;
+
+        if tokens.first().map(|t| t.ttype) == Some(TokenType::CloseParen) {
+            return Ok((params, variadic, 1));
+        }
+
+        loop {
+            match tokens.get(i) {
+                Some(token) if token.ttype == TokenType::Ellipsis => variadic = true,
+                Some(token) if token.is_ident() && token.text != VA_ARGS => {
+                    if params.contains(&token.text) {
+                        return Err(self.error(
+                            PreprocessErrorKind::DuplicateMacroParam {
+                                name: token.text.clone(),
+                            },
+                            token,
+                        ));
+                    }
+                    params.push(token.text.clone());
+                }
+                token => return Err(invalid(token)),
+            }
+
+            // Nothing can come after the ellipsis
+            match tokens.get(i + 1) {
+                Some(token) if token.ttype == TokenType::CloseParen => {
+                    return Ok((params, variadic, i + 2));
+                }
+                Some(token) if token.ttype == TokenType::Comma && !variadic => i += 2,
+                token => return Err(invalid(token.or(tokens.get(i)))),
+            }
+        }
+    }
+
+    /// Expands `token` if it's a macro, pushing the expansion back onto `input` so it gets
+    /// rescanned. Returns whether there was an expansion.
+    pub(crate) fn expand_macro(
+        &mut self,
+        token: &PpToken,
+        input: &mut Vec<PpToken>,
+    ) -> PreprocessResult<bool> {
+        if !token.is_ident() || token.hide_set.contains(&token.text) {
+            return Ok(false);
+        }
+
+        if let Some(builtin) = self.builtin(token) {
+            input.push(builtin);
+            return Ok(true);
+        }
+
+        let Some(m) = self.macros.get(&token.text).cloned() else {
+            return Ok(false);
+        };
+
+        let (mut expansion, mut hide_set) = match &m.params {
+            None => (m.body.clone(), token.hide_set.clone()),
+            Some(_) => {
+                if input.last().map(|t| t.ttype) != Some(TokenType::OpenParen) {
+                    return Ok(false);
+                }
+
+                let (args, close_paren) = self.macro_args(token, &m, input)?;
+
+                // Only macros which were hidden at both the name and the closing paren stay
+                // hidden, since the arguments may come from outside the expansion which hid them
+                let hide_set = token
+                    .hide_set
+                    .iter()
+                    .filter(|name| close_paren.hide_set.contains(name))
+                    .cloned()
+                    .collect();
+
+                (self.substitute(&m, &args)?, hide_set)
+            }
+        };
+
+        hide_set.push(token.text.clone());
+        let expanded_from = token.expanded_from.unwrap_or(token.location);
+
+        for (i, t) in expansion.iter_mut().enumerate() {
+            t.hide_set.extend(hide_set.iter().cloned());
+            t.expanded_from = Some(expanded_from);
+            t.at_line_start = false;
+
+            if i == 0 {
+                t.leading_space = token.leading_space;
+            }
+        }
+
+        input.extend(expansion.into_iter().rev());
+
+        Ok(true)
+    }
+
+    /// Fully expands a list of tokens on its own
+    pub(crate) fn expand_all(&mut self, tokens: Vec<PpToken>) -> PreprocessResult<Vec<PpToken>> {
+        let mut input: Vec<_> = tokens.into_iter().rev().collect();
+        let mut output = Vec::new();
+
+        while let Some(token) = input.pop() {
+            if !self.expand_macro(&token, &mut input)? {
+                output.push(token);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Expansion of `__FILE__` or `__LINE__`, which refer to where they're used
+    fn builtin(&self, token: &PpToken) -> Option<PpToken> {
+        let location = token.presumed_location();
+
+        let (ttype, text) = match token.text.as_str() {
+            "__FILE__" => (
+                TokenType::StringLiteral,
+                quote(&self.sources.path(location.file).display().to_string()),
+            ),
+            "__LINE__" => (
+                TokenType::IntConstant {
+                    radix: Radix::Decimal,
+                    suffix: IntSuffix::None,
+                },
+                (location.position.line + 1).to_string(),
+            ),
+            _ => return None,
+        };
+
+        Some(PpToken {
+            ttype,
+            text,
+            at_line_start: false,
+            expanded_from: Some(location),
+            ..token.clone()
+        })
+    }
+
+    /// Pops the arguments of a call to the function-like macro `m`, whose opening paren is the
+    /// next token in `input`. Also returns the closing paren.
+    fn macro_args(
+        &self,
+        name: &PpToken,
+        m: &Macro,
+        input: &mut Vec<PpToken>,
+    ) -> PreprocessResult<(Vec<Vec<PpToken>>, PpToken)> {
+        let params = m
+            .params
+            .as_ref()
+            .expect("Only function-like macros have args");
+
+        input.pop();
+
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+
+        let close_paren = loop {
+            let Some(token) = input.pop() else {
+                return Err(self.error(
+                    PreprocessErrorKind::UnterminatedMacroCall {
+                        name: name.text.clone(),
+                    },
+                    name,
+                ));
+            };
+
+            match token.ttype {
+                TokenType::CloseParen if depth == 0 => break token,
+                // Everything after the named parameters goes into `__VA_ARGS__`, commas included
+                TokenType::Comma if depth == 0 && !(m.variadic && args.len() > params.len()) => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                TokenType::OpenParen => depth += 1,
+                TokenType::CloseParen => depth -= 1,
+                _ => (),
+            }
+
+            args.last_mut().unwrap().push(token);
+        };
+
+        // `F()` passes a single empty argument, which is the same as passing none
+        if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+
+        // The variadic arguments can be left out entirely
+        if m.variadic && args.len() == params.len() {
+            args.push(Vec::new());
+        }
+
+        let expected = params.len() + m.variadic as usize;
+        if args.len() != expected {
+            return Err(self.error(
+                PreprocessErrorKind::WrongArgCount {
+                    name: name.text.clone(),
+                    expected: params.len(),
+                    found: args.len(),
+                },
+                name,
+            ));
+        }
+
+        Ok((args, close_paren))
+    }
+
+    /// Replaces the parameters in the body of `m` with `args`, stringizing and pasting as needed
+    fn substitute(&mut self, m: &Macro, args: &[Vec<PpToken>]) -> PreprocessResult<Vec<PpToken>> {
+        let body = &m.body;
+        let mut output: Vec<PpToken> = Vec::new();
+        let mut i = 0;
+
+        while i < body.len() {
+            let token = &body[i];
+            let param = m.param(token);
+
+            if token.ttype == TokenType::Hash
+                && let Some(arg) = body.get(i + 1).and_then(|t| m.param(t))
+            {
+                output.push(stringize(token, &args[arg]));
+                i += 2;
+            } else if token.ttype == TokenType::HashHash {
+                let right = &body[i + 1];
+                let right = match m.param(right) {
+                    Some(arg) => args[arg].clone(),
+                    None => vec![right.clone()],
+                };
+
+                // An empty argument acts as a placemarker, so pasting with it gives the other
+                // side unchanged
+                match (output.pop(), right.split_first()) {
+                    (Some(left), Some((first, rest))) if !left.text.is_empty() => {
+                        output.push(self.paste(&left, first)?);
+                        output.extend_from_slice(rest);
+                    }
+                    (_, Some(_)) => output.extend(right),
+                    (Some(left), None) => output.push(left),
+                    (None, None) => (),
+                }
+                i += 2;
+            } else if let Some(arg) = param {
+                // Arguments are only expanded when they aren't an operand of '##'
+                if body.get(i + 1).map(|t| t.ttype) == Some(TokenType::HashHash) {
+                    match args[arg].is_empty() {
+                        true => output.push(placemarker(token)),
+                        false => output.extend(args[arg].iter().cloned()),
+                    }
+                } else {
+                    let mut expanded = self.expand_all(args[arg].clone())?;
+                    if let Some(first) = expanded.first_mut() {
+                        first.leading_space = token.leading_space;
+                    }
+                    output.extend(expanded);
+                }
+                i += 1;
+            } else {
+                output.push(token.clone());
+                i += 1;
+            }
+        }
+
+        output.retain(|token| !token.text.is_empty());
+
+        Ok(output)
+    }
+
+    /// Joins two tokens into one, which must be a valid token
+    fn paste(&self, left: &PpToken, right: &PpToken) -> PreprocessResult<PpToken> {
+        let text = format!("{}{}", left.text, right.text);
+
+        match Lexer::lex(&text) {
+            Ok(tokens) if tokens.len() == 1 && tokens.token_text(0) == text => Ok(PpToken {
+                ttype: tokens.get(0).unwrap().ttype,
+                text,
+                ..left.clone()
+            }),
+            _ => Err(self.error(
+                PreprocessErrorKind::InvalidPaste {
+                    left: left.text.clone(),
+                    right: right.text.clone(),
+                },
+                left,
+            )),
+        }
+    }
+}
+
+/// An empty token, which stands in for an empty argument next to a '##' until pasting is done
+fn placemarker(param: &PpToken) -> PpToken {
+    PpToken {
+        ttype: TokenType::Unknown,
+        text: String::new(),
+        ..param.clone()
+    }
+}
+
+/// Turns the tokens of an argument into a string literal, as done by the '#' operator
+fn stringize(hash: &PpToken, arg: &[PpToken]) -> PpToken {
+    PpToken {
+        ttype: TokenType::StringLiteral,
+        text: quote(&render_line(arg)),
+        ..hash.clone()
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+use lex::{
+    Lexer,
+    source_map::{FileId, SourceMap},
+};
+
+use crate::{quote, token::PpToken};
+
+/// Tokens left after preprocessing, along with every file they came from
+#[derive(Debug, Clone)]
+pub struct PreprocessedOutput {
+    sources: SourceMap,
+    /// The file which was preprocessed, as opposed to the ones it included
+    main: FileId,
+    tokens: Vec<PpToken>,
+}
+
+impl PreprocessedOutput {
+    pub(crate) fn new(sources: SourceMap, main: FileId, tokens: Vec<PpToken>) -> Self {
+        PreprocessedOutput {
+            sources,
+            main,
+            tokens,
+        }
+    }
+
+    pub fn tokens(&self) -> &[PpToken] {
+        &self.tokens
+    }
+
+    /// Path of the file a `Location` refers to
+    pub fn path(&self, file: FileId) -> &Path {
+        self.sources.path(file)
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+}
+
+/// Whether two tokens would lex differently if written next to each other, like '-' and '-1'
+fn would_merge(left: &PpToken, right: &PpToken) -> bool {
+    let text = format!("{}{}", left.text, right.text);
+
+    Lexer::lex(&text).map_or(true, |tokens| {
+        tokens.len() != 2 || tokens.token_text(0) != left.text
+    })
+}
+
+/// Renders the tokens back into source code
+///
+/// Tokens are kept in the same line and column they were written in, and macro expansions are
+/// placed where the macro was used. Whenever the output switches files or goes back to a previous
+/// line, a linemarker (`# 42 "file.c"`) says where the following lines come from, so positions in
+/// the output can be traced back to the original files.
+impl Display for PreprocessedOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut previous: Option<&PpToken> = None;
+        let mut file = self.path(self.main);
+        let mut line = 0;
+        let mut column = 0;
+
+        for token in &self.tokens {
+            let location = token.presumed_location();
+            let position = location.position;
+
+            let new_line = if self.path(location.file) != file || position.line < line {
+                if previous.is_some() {
+                    writeln!(f)?;
+                }
+                file = self.path(location.file);
+                writeln!(
+                    f,
+                    "# {} {}",
+                    position.line + 1,
+                    quote(&file.display().to_string())
+                )?;
+                true
+            } else if previous.is_none() || position.line > line {
+                for _ in line..position.line {
+                    writeln!(f)?;
+                }
+                true
+            } else {
+                false
+            };
+
+            if new_line {
+                line = position.line;
+                column = 0;
+            }
+
+            // Tokens written in the file are kept in their column, while tokens from macros only
+            // keep track of whether they had whitespace before them
+            let mut padding = if token.expanded_from.is_none() && (new_line || token.leading_space)
+            {
+                position
+                    .column
+                    .saturating_sub(column)
+                    .max(!new_line as usize)
+            } else {
+                token.leading_space as usize
+            };
+
+            if let Some(previous) = previous
+                && padding == 0
+                && !new_line
+                && (previous.expanded_from.is_some() || token.expanded_from.is_some())
+                && would_merge(previous, token)
+            {
+                padding = 1;
+            }
+
+            write!(f, "{:padding$}{}", "", token.text)?;
+            column += padding + token.text.chars().count();
+            previous = Some(token);
+        }
+
+        Ok(())
+    }
+}
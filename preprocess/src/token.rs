@@ -0,0 +1,46 @@
+use lex::{TokenType, line::Position, source_map::FileId, token::Span};
+
+/// Where a token was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// File the token was written in, which `#line` may have renamed
+    pub file: FileId,
+    /// Where the token is in the file's source text
+    pub span: Span,
+    /// Line and column the token is reported at, which `#line` may have changed
+    pub position: Position,
+}
+
+/// A preprocessing token
+///
+/// Unlike lexer tokens, these own their text since they can come from several files and be created
+/// by macro expansion (stringification, pasting, `__LINE__`, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PpToken {
+    pub ttype: TokenType,
+    pub text: String,
+    /// Whether there's whitespace between this token and the previous one
+    pub leading_space: bool,
+    /// Whether this is the first token of a line, never set for tokens produced by macros
+    pub at_line_start: bool,
+    /// Where the token was originally written
+    pub location: Location,
+    /// Location of the outermost macro invocation this token comes from, if any
+    pub expanded_from: Option<Location>,
+    /// Macros which must not be expanded again from this token, this is what stops self-referential
+    /// macros like `#define foo foo` from expanding forever
+    pub(crate) hide_set: Vec<String>,
+}
+
+impl PpToken {
+    /// Where the token should be reported as being: its macro invocation if it comes from a macro,
+    /// or where it was written otherwise
+    pub fn presumed_location(&self) -> Location {
+        self.expanded_from.unwrap_or(self.location)
+    }
+
+    pub(crate) fn is_ident(&self) -> bool {
+        // Keywords don't exist while preprocessing, so they can be macro names or parameters
+        matches!(self.ttype, TokenType::Ident | TokenType::Keyword(_))
+    }
+}
@@ -0,0 +1,67 @@
+use std::{error::Error, fmt::Display};
+
+use lex::{source_map::FileId, token::Span};
+
+/// An error in a program which parsed fine, but doesn't make sense
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SemaError {
+    pub kind: SemaErrorKind,
+    pub file: FileId,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemaErrorKind {
+    UndeclaredVariable {
+        name: String,
+    },
+    Redeclaration {
+        name: String,
+    },
+    /// Assignment to something other than a variable
+    InvalidLvalue,
+}
+
+impl Display for SemaErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SemaErrorKind::UndeclaredVariable { name } => {
+                write!(f, "Use of undeclared variable '{name}'")
+            }
+            SemaErrorKind::Redeclaration { name } => {
+                write!(f, "Redeclaration of variable '{name}'")
+            }
+            SemaErrorKind::InvalidLvalue => {
+                write!(f, "Invalid lvalue, only variables can be assigned to")
+            }
+        }
+    }
+}
+
+impl Display for SemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Error for SemaError {}
+
+/// Every semantic error found in a program, in the order they appear in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SemaErrors {
+    pub errors: Vec<SemaError>,
+}
+
+impl Display for SemaErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SemaErrors {}
@@ -0,0 +1,322 @@
+//! Semantic analysis, which checks what the grammar alone can't, like whether variables are
+//! declared before they're used
+//!
+//! Every variable is also renamed to a name unique in the whole program, so later stages don't
+//! need to know about scopes to tell shadowed variables apart.
+
+use std::collections::HashMap;
+
+use ast::{BlockId, BlockItem, Decl, Expr, ExprId, IdentId, NodeKind, Stmt, StmtId, Tree};
+use lex::{Token, symbol::Symbol};
+use tracing::{Level, span};
+
+pub mod error;
+pub use error::*;
+
+pub struct Resolver<'a> {
+    tree: &'a mut Tree,
+    /// One map per enclosing block, innermost last, from each name declared in it to its unique
+    /// name
+    scopes: Vec<HashMap<Symbol, Symbol>>,
+    /// Number of variables renamed so far, which keeps their unique names apart
+    variables: usize,
+    errors: Vec<SemaError>,
+}
+
+/// Checks the whole program, failing with every error found if there's at least one
+pub fn resolve(tree: &mut Tree) -> Result<(), SemaErrors> {
+    let _ = span!(Level::TRACE, "Semantic analysis").entered();
+
+    let mut resolver = Resolver::new(tree);
+    resolver.resolve_program();
+
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SemaErrors {
+            errors: resolver.errors,
+        })
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(tree: &'a mut Tree) -> Resolver<'a> {
+        Resolver {
+            tree,
+            scopes: Vec::new(),
+            variables: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, kind: SemaErrorKind, token: Token) {
+        let source = self.tree.tokens.token_source(token.handle);
+
+        self.errors.push(SemaError {
+            kind,
+            file: source.file,
+            span: source.span,
+        });
+    }
+
+    fn name(&self, ident: IdentId) -> String {
+        self.tree.tokens.symbols()[self.tree[ident].name].to_string()
+    }
+
+    /// Reports an error at the operator `token` unless `expr` can be assigned to
+    fn check_lvalue(&mut self, expr: ExprId, token: Token) {
+        if !matches!(self.tree[expr], Expr::Var { .. }) {
+            self.error(SemaErrorKind::InvalidLvalue, token);
+        }
+    }
+
+    fn resolve_program(&mut self) {
+        let NodeKind::Program(program) = self.tree.program_node().kind else {
+            unreachable!()
+        };
+        let main = self.tree[program.main];
+
+        self.resolve_block(main.body);
+    }
+
+    fn resolve_block(&mut self, block: BlockId) {
+        let items = self.tree[block].items;
+
+        self.scopes.push(HashMap::new());
+        for i in 0..self.tree[items].len() {
+            match self.tree[items][i] {
+                BlockItem::Stmt(stmt) => self.resolve_stmt(stmt),
+                BlockItem::Decl(decl) => self.resolve_decl(self.tree[decl]),
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_decl(&mut self, decl: Decl) {
+        let ident = self.tree[decl.name];
+        let name = self.name(decl.name);
+
+        let scope = self
+            .scopes
+            .last()
+            .expect("Declarations are always inside a block");
+        if scope.contains_key(&ident.name) {
+            self.error(SemaErrorKind::Redeclaration { name }, ident.token);
+        } else {
+            let unique = format!("{name}.{}", self.variables);
+            self.variables += 1;
+            let unique = self.tree.tokens.symbols_mut().intern(&unique);
+
+            self.scopes
+                .last_mut()
+                .expect("Declarations are always inside a block")
+                .insert(ident.name, unique);
+            self.tree.rename(decl.name, unique);
+        }
+
+        // The variable is already in scope in its own initializer
+        if let Some(init) = decl.init {
+            self.resolve_expr(init);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: StmtId) {
+        match self.tree[stmt] {
+            Stmt::Return { expr, .. } | Stmt::Expression { expr } => self.resolve_expr(expr),
+            Stmt::If {
+                cond,
+                cond_true,
+                cond_false,
+            } => {
+                self.resolve_expr(cond);
+                self.resolve_stmt(cond_true);
+                if let Some(cond_false) = cond_false {
+                    self.resolve_stmt(cond_false);
+                }
+            }
+            Stmt::Compound { block } => self.resolve_block(block),
+            Stmt::Null => (),
+            Stmt::Error { .. } => unreachable!("Trees with errors are never analyzed"),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: ExprId) {
+        match self.tree[expr] {
+            Expr::Constant { .. } => (),
+            Expr::Var { ident } => {
+                let name = self.tree[ident].name;
+                let unique = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.get(&name).copied());
+
+                match unique {
+                    Some(unique) => self.tree.rename(ident, unique),
+                    None => {
+                        let name = self.name(ident);
+                        self.error(
+                            SemaErrorKind::UndeclaredVariable { name },
+                            self.tree[ident].token,
+                        );
+                    }
+                }
+            }
+            Expr::Assign { dst, src, token }
+            | Expr::CompoundAssign {
+                dst, src, token, ..
+            } => {
+                self.check_lvalue(dst, token);
+                self.resolve_expr(dst);
+                self.resolve_expr(src);
+            }
+            Expr::Increment { operand, token, .. } => {
+                self.check_lvalue(operand, token);
+                self.resolve_expr(operand);
+            }
+            Expr::Conditional {
+                cond,
+                cond_true,
+                cond_false,
+            } => {
+                self.resolve_expr(cond);
+                self.resolve_expr(cond_true);
+                self.resolve_expr(cond_false);
+            }
+            Expr::Unary { operand, .. } => self.resolve_expr(operand),
+            Expr::Binary { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::NodeKind;
+    use lex::Lexer;
+    use parse::Parser;
+
+    use crate::{SemaErrorKind, resolve};
+
+    fn errors(source: &str) -> Vec<SemaErrorKind> {
+        let tokens = Lexer::lex(source).unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+
+        match resolve(&mut parser.nodes) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.errors.into_iter().map(|error| error.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let source = "int main(void) { int a = 1; int b; b = a = 2; return a + b; }";
+
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn own_initializer() {
+        assert_eq!(errors("int main(void) { int a = 0 & a; return a; }"), []);
+    }
+
+    #[test]
+    fn undeclared() {
+        assert_eq!(
+            errors("int main(void) { a = 1; int a; return -b; }"),
+            [
+                SemaErrorKind::UndeclaredVariable { name: "a".into() },
+                SemaErrorKind::UndeclaredVariable { name: "b".into() }
+            ]
+        );
+    }
+
+    #[test]
+    fn redeclaration() {
+        assert_eq!(
+            errors("int main(void) { int a = 1; return a; int a; }"),
+            [SemaErrorKind::Redeclaration { name: "a".into() }]
+        );
+    }
+
+    #[test]
+    fn invalid_increment() {
+        assert_eq!(
+            errors("int main(void) { int a; a++; --a; 5++; ++(a + 1); a++--; a += 1 -= 2; }"),
+            [
+                SemaErrorKind::InvalidLvalue,
+                SemaErrorKind::InvalidLvalue,
+                SemaErrorKind::InvalidLvalue,
+                SemaErrorKind::InvalidLvalue
+            ]
+        );
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!(
+            errors("int main(void) { int a; if (a) a = 1 ? b : 2; else a ? 1 : 2 = 3; }"),
+            [
+                SemaErrorKind::UndeclaredVariable { name: "b".into() },
+                SemaErrorKind::InvalidLvalue
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        let source = "int main(void) { int a = 1; { int a = a + 1; { a = 3; } } int b; return a; }";
+
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn out_of_scope() {
+        assert_eq!(
+            errors("int main(void) { { int a = 1; } if (1) { int b; } return a + b; }"),
+            [
+                SemaErrorKind::UndeclaredVariable { name: "a".into() },
+                SemaErrorKind::UndeclaredVariable { name: "b".into() }
+            ]
+        );
+    }
+
+    #[test]
+    fn redeclaration_in_block() {
+        assert_eq!(
+            errors("int main(void) { int a; { int a; int b; int a; } }"),
+            [SemaErrorKind::Redeclaration { name: "a".into() }]
+        );
+    }
+
+    #[test]
+    fn unique_names() {
+        let tokens = Lexer::lex("int main(void) { int a; { int a = a; } return a; }").unwrap();
+        let mut parser = Parser::from_tokens(tokens);
+        parser.parse().unwrap();
+        resolve(&mut parser.nodes).unwrap();
+
+        let tree = &parser.nodes;
+        let names: Vec<_> = tree
+            .nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Ident(ident) => Some(&tree.tokens.symbols()[ident.name]),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(names, ["main", "a.0", "a.1", "a.1", "a.0"]);
+    }
+
+    #[test]
+    fn invalid_lvalue() {
+        assert_eq!(
+            errors("int main(void) { int a; a = 3 * a = 1; !a = 2; }"),
+            [SemaErrorKind::InvalidLvalue, SemaErrorKind::InvalidLvalue]
+        );
+    }
+}
@@ -1,16 +1,18 @@
 use std::fmt::{Display, Formatter};
 
+use lex::symbol::SymbolTable;
+
 use crate::{FnDef, Instruction, Operand, Program, X86};
 
 impl Display for X86 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.nodes.fmt_node(0, f);
+        self.nodes.fmt_node(0, &self.symbols, f);
         Ok(())
     }
 }
 
 pub trait DisplayNode {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>);
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>);
     fn write(&self, indent: usize, string: &str, f: &mut Formatter<'_>) {
         write!(f, "{}{}", " ".repeat(indent), string).unwrap();
     }
@@ -20,43 +22,91 @@ pub trait DisplayNode {
 }
 
 impl DisplayNode for X86 {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>) {
-        self.nodes.fmt_node(indent, f);
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>) {
+        self.nodes.fmt_node(indent, symbols, f);
     }
 }
 
 impl DisplayNode for Program {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>) {
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>) {
         self.writeln(indent, "Program(", f);
-        self.main.fmt_node(indent + 1, f);
+        self.main.fmt_node(indent + 1, symbols, f);
         self.writeln(indent, ")", f);
     }
 }
 
 impl DisplayNode for FnDef {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>) {
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>) {
         self.writeln(indent, "Function(", f);
-        self.writeln(indent + 1, &format!("name = {}", self.name), f);
+        self.writeln(indent + 1, &format!("name = {}", &symbols[self.name]), f);
         self.writeln(indent + 1, "instructions = [", f);
         self.body
             .iter()
-            .for_each(|instr| instr.fmt_node(indent + 2, f));
+            .for_each(|instr| instr.fmt_node(indent + 2, symbols, f));
         self.writeln(indent + 1, "]", f);
         self.writeln(indent, ")", f);
     }
 }
 
 impl DisplayNode for Instruction {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>) {
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>) {
         self.write(indent, "Instruction [", f);
         match self {
             Instruction::Mov { src, dst } => {
                 self.write(0, "Mov(src: ", f);
-                src.fmt_node(0, f);
+                src.fmt_node(0, symbols, f);
+                self.write(0, ", dest: ", f);
+                dst.fmt_node(0, symbols, f);
+                self.write(0, ")", f);
+            }
+            Instruction::Unary { op, operand } => {
+                self.write(0, &format!("Unary(op: {op:?}, operand: "), f);
+                operand.fmt_node(0, symbols, f);
+                self.write(0, ")", f);
+            }
+            Instruction::Binary { op, src, dst } => {
+                self.write(0, &format!("Binary(op: {op:?}, src: "), f);
+                src.fmt_node(0, symbols, f);
+                self.write(0, ", dest: ", f);
+                dst.fmt_node(0, symbols, f);
+                self.write(0, ")", f);
+            }
+            Instruction::Cdq => {
+                self.write(0, "Cdq", f);
+            }
+            Instruction::Idiv(operand) => {
+                self.write(0, "Idiv(", f);
+                operand.fmt_node(0, symbols, f);
+                self.write(0, ")", f);
+            }
+            Instruction::Cmp { src, dst } => {
+                self.write(0, "Cmp(src: ", f);
+                src.fmt_node(0, symbols, f);
                 self.write(0, ", dest: ", f);
-                dst.fmt_node(0, f);
+                dst.fmt_node(0, symbols, f);
+                self.write(0, ")", f);
+            }
+            Instruction::SetCC { cond, dst } => {
+                self.write(0, &format!("SetCC(cond: {cond:?}, dest: "), f);
+                dst.fmt_node(0, symbols, f);
                 self.write(0, ")", f);
             }
+            Instruction::Jmp(target) => {
+                self.write(0, &format!("Jmp({})", &symbols[*target]), f);
+            }
+            Instruction::JmpCC { cond, target } => {
+                self.write(
+                    0,
+                    &format!("JmpCC(cond: {cond:?}, target: {})", &symbols[*target]),
+                    f,
+                );
+            }
+            Instruction::Label(name) => {
+                self.write(0, &format!("Label({})", &symbols[*name]), f);
+            }
+            Instruction::AllocateStack(size) => {
+                self.write(0, &format!("AllocateStack({size})"), f);
+            }
             Instruction::Ret => {
                 self.write(0, "Ret", f);
             }
@@ -66,10 +116,12 @@ impl DisplayNode for Instruction {
 }
 
 impl DisplayNode for Operand {
-    fn fmt_node(&self, indent: usize, f: &mut Formatter<'_>) {
+    fn fmt_node(&self, indent: usize, symbols: &SymbolTable, f: &mut Formatter<'_>) {
         match self {
             Operand::Immediate(value) => self.write(indent, &format!("Immediate({})", value), f),
-            Operand::Register => self.write(indent, "Register", f),
+            Operand::Register(reg) => self.write(indent, &format!("Register({reg:?})"), f),
+            Operand::Pseudo(name) => self.write(indent, &format!("Pseudo({})", &symbols[*name]), f),
+            Operand::Stack(offset) => self.write(indent, &format!("Stack({offset})"), f),
         }
     }
 }
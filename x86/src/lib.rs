@@ -1,13 +1,17 @@
 use ast::Tree;
-use lower::Lower;
+use lex::symbol::{Symbol, SymbolTable};
+use lower::{Context, Lower};
 use tracing::{Level, span};
 
 pub mod fmt;
 pub mod lower;
+mod passes;
 
 #[derive(Debug, PartialEq)]
 pub struct X86 {
     pub nodes: Program,
+    /// Names used by the program, carried over from the AST
+    pub symbols: SymbolTable,
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,29 +29,114 @@ pub struct Program {
 
 #[derive(Debug, PartialEq)]
 pub struct FnDef {
-    pub name: String,
+    pub name: Symbol,
     pub body: Vec<Instruction>,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Instruction {
-    Mov { src: Operand, dst: Operand },
+    Mov {
+        src: Operand,
+        dst: Operand,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Operand,
+    },
+    Binary {
+        op: BinaryOp,
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sign extends %eax into %edx, ahead of a division
+    Cdq,
+    /// Divides %edx:%eax by the operand, leaving the quotient in %eax and the remainder in %edx
+    Idiv(Operand),
+    /// Sets the flags according to `dst - src`
+    Cmp {
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sets the lowest byte of `dst` to 1 if `cond` holds, or to 0 otherwise
+    SetCC {
+        cond: CondCode,
+        dst: Operand,
+    },
+    Jmp(Symbol),
+    /// Jumps to the label if `cond` holds
+    JmpCC {
+        cond: CondCode,
+        target: Symbol,
+    },
+    Label(Symbol),
+    /// Reserves this many bytes of the stack for the current function
+    AllocateStack(i64),
     Ret,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mult,
+    And,
+    Or,
+    Xor,
+    /// Shift left, the count has to be a constant or %cl
+    Sal,
+    /// Arithmetic shift right, which keeps the sign, the count has to be a constant or %cl
+    Sar,
+    /// Logical shift right, which fills with zeros, the count has to be a constant or %cl
+    Shr,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CondCode {
+    E,
+    NE,
+    L,
+    LE,
+    G,
+    GE,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Operand {
     Immediate(i64),
-    Register,
+    Register(Register),
+    /// A temporary value, which gets a place in the stack once the whole function is lowered
+    Pseudo(Symbol),
+    /// Offset from the frame pointer, %rbp
+    Stack(i64),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Register {
+    Ax,
+    Cx,
+    Dx,
+    /// Scratch register used when an instruction can't take its operands as they are
+    R10,
+    /// Scratch register used when an instruction can't take its operands as they are
+    R11,
 }
 
 pub fn lower(input: &Tree) -> X86 {
     let _ = span!(Level::TRACE, "X86 lowering");
 
-    X86 {
-        nodes: match input.program_node().kind {
-            ast::NodeKind::Program(program) => program.lower(input),
-            _ => unreachable!(),
-        },
-    }
+    let mut symbols = input.tokens.symbols().clone();
+    let mut ctx = Context::new(input, &mut symbols);
+
+    let nodes = match input.program_node().kind {
+        ast::NodeKind::Program(program) => program.lower(&mut ctx),
+        _ => unreachable!(),
+    };
+
+    X86 { nodes, symbols }
 }
@@ -1,34 +1,167 @@
 use ast::Tree;
+use lex::symbol::{Symbol, SymbolTable};
 
-use crate::{FnDef, Instruction, NodeKind, Operand, Program};
+use crate::{
+    BinaryOp, CondCode, FnDef, Instruction, NodeKind, Operand, Program, Register, UnaryOp, passes,
+};
+
+/// Everything lowering needs besides the node being lowered
+pub struct Context<'a> {
+    pub tree: &'a Tree,
+    /// Names of the program, which temporaries are added to
+    pub symbols: &'a mut SymbolTable,
+    /// Instructions of the function being lowered so far
+    instructions: Vec<Instruction>,
+    temporaries: usize,
+    labels: usize,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(tree: &'a Tree, symbols: &'a mut SymbolTable) -> Context<'a> {
+        Context {
+            tree,
+            symbols,
+            instructions: Vec::new(),
+            temporaries: 0,
+            labels: 0,
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    /// A new temporary, named so it can't clash with any variable, even one renamed to `tmp.N`
+    fn temporary(&mut self) -> Operand {
+        loop {
+            let name = format!("tmp.{}", self.temporaries);
+            self.temporaries += 1;
+
+            if self.symbols.get(&name).is_none() {
+                return Operand::Pseudo(self.symbols.intern(&name));
+            }
+        }
+    }
+
+    /// A new label, `name` describes it and a number makes it unique
+    fn label(&mut self, name: &str) -> Symbol {
+        let name = format!("{name}.{}", self.labels);
+        self.labels += 1;
+
+        self.symbols.intern(&name)
+    }
+
+    /// Computes `left op right` into `dst`, for any binary operator which doesn't short circuit
+    fn binary(&mut self, op: ast::BinaryOp, left: Operand, right: Operand, dst: Operand) {
+        let op = match op {
+            ast::BinaryOp::Add => BinaryOp::Add,
+            ast::BinaryOp::Subtract => BinaryOp::Sub,
+            ast::BinaryOp::Multiply => BinaryOp::Mult,
+            ast::BinaryOp::BitAnd => BinaryOp::And,
+            ast::BinaryOp::BitOr => BinaryOp::Or,
+            ast::BinaryOp::BitXor => BinaryOp::Xor,
+            ast::BinaryOp::ShiftLeft => BinaryOp::Sal,
+            // Every value is a signed int so far, unsigned ones will need `Shr` instead
+            ast::BinaryOp::ShiftRight => BinaryOp::Sar,
+            ast::BinaryOp::Equal
+            | ast::BinaryOp::NotEqual
+            | ast::BinaryOp::LessThan
+            | ast::BinaryOp::LessOrEqual
+            | ast::BinaryOp::GreaterThan
+            | ast::BinaryOp::GreaterOrEqual => {
+                let cond = match op {
+                    ast::BinaryOp::Equal => CondCode::E,
+                    ast::BinaryOp::NotEqual => CondCode::NE,
+                    ast::BinaryOp::LessThan => CondCode::L,
+                    ast::BinaryOp::LessOrEqual => CondCode::LE,
+                    ast::BinaryOp::GreaterThan => CondCode::G,
+                    _ => CondCode::GE,
+                };
+
+                self.push(Instruction::Cmp {
+                    src: right,
+                    dst: left,
+                });
+                self.push(Instruction::Mov {
+                    src: Operand::Immediate(0),
+                    dst,
+                });
+                self.push(Instruction::SetCC { cond, dst });
+
+                return;
+            }
+            ast::BinaryOp::And | ast::BinaryOp::Or => {
+                unreachable!("Logical operators short circuit, so they're lowered on their own")
+            }
+            ast::BinaryOp::Divide | ast::BinaryOp::Remainder => {
+                let result = match op {
+                    ast::BinaryOp::Divide => Register::Ax,
+                    _ => Register::Dx,
+                };
+
+                self.push(Instruction::Mov {
+                    src: left,
+                    dst: Operand::Register(Register::Ax),
+                });
+                self.push(Instruction::Cdq);
+                self.push(Instruction::Idiv(right));
+                self.push(Instruction::Mov {
+                    src: Operand::Register(result),
+                    dst,
+                });
+
+                return;
+            }
+        };
+
+        // Compound assignments operate on the variable itself
+        if left != dst {
+            self.push(Instruction::Mov { src: left, dst });
+        }
+        self.push(Instruction::Binary {
+            op,
+            src: right,
+            dst,
+        });
+    }
+}
 
 pub trait Lower {
     type Output;
 
-    fn lower(&self, tree: &Tree) -> Self::Output;
+    fn lower(&self, ctx: &mut Context) -> Self::Output;
 }
 
 impl Lower for ast::Node {
     type Output = NodeKind;
 
-    fn lower(&self, tree: &Tree) -> NodeKind {
-        self.kind.lower(tree)
+    fn lower(&self, ctx: &mut Context) -> NodeKind {
+        self.kind.lower(ctx)
     }
 }
 
 impl Lower for ast::NodeKind {
     type Output = NodeKind;
 
-    fn lower(&self, tree: &Tree) -> NodeKind {
+    fn lower(&self, ctx: &mut Context) -> NodeKind {
         match self {
-            ast::NodeKind::Program(program) => NodeKind::Program(program.lower(tree)),
-            ast::NodeKind::FnDef(fn_def) => NodeKind::FnDef(fn_def.lower(tree)),
-            ast::NodeKind::Stmt(stmt) => NodeKind::Instructions(stmt.lower(tree)),
-            ast::NodeKind::Expr(expr) => NodeKind::Operand(Operand::Immediate(expr.lower(tree))),
-            ast::NodeKind::Ident(_ident) => todo!(),
-            ast::NodeKind::Constant(constant) => {
-                NodeKind::Operand(Operand::Immediate(constant.lower(tree)))
+            ast::NodeKind::Program(program) => NodeKind::Program(program.lower(ctx)),
+            ast::NodeKind::FnDef(fn_def) => NodeKind::FnDef(fn_def.lower(ctx)),
+            ast::NodeKind::Block(block) => {
+                block.lower(ctx);
+                NodeKind::Instructions(std::mem::take(&mut ctx.instructions))
             }
+            ast::NodeKind::Decl(decl) => {
+                decl.lower(ctx);
+                NodeKind::Instructions(std::mem::take(&mut ctx.instructions))
+            }
+            ast::NodeKind::Stmt(stmt) => {
+                stmt.lower(ctx);
+                NodeKind::Instructions(std::mem::take(&mut ctx.instructions))
+            }
+            ast::NodeKind::Expr(expr) => NodeKind::Operand(expr.lower(ctx)),
+            ast::NodeKind::Ident(ident) => NodeKind::Operand(ident.lower(ctx)),
+            ast::NodeKind::Constant(constant) => NodeKind::Operand(constant.lower(ctx)),
         }
     }
 }
@@ -36,9 +169,9 @@ impl Lower for ast::NodeKind {
 impl Lower for ast::Program {
     type Output = Program;
 
-    fn lower(&self, tree: &Tree) -> Program {
+    fn lower(&self, ctx: &mut Context) -> Program {
         Program {
-            main: tree[self.main].lower(tree),
+            main: ctx.tree[self.main].lower(ctx),
         }
     }
 }
@@ -46,50 +179,319 @@ impl Lower for ast::Program {
 impl Lower for ast::FnDef {
     type Output = FnDef;
 
-    fn lower(&self, tree: &Tree) -> FnDef {
+    fn lower(&self, ctx: &mut Context) -> FnDef {
+        ctx.tree[self.body].lower(ctx);
+
+        // Falling off the end of `main` returns 0, and it's undefined behavior for any other
+        // function so they might as well do the same
+        if !matches!(ctx.instructions.last(), Some(Instruction::Ret)) {
+            ctx.push(Instruction::Mov {
+                src: Operand::Immediate(0),
+                dst: Operand::Register(Register::Ax),
+            });
+            ctx.push(Instruction::Ret);
+        }
+
+        let mut body = std::mem::take(&mut ctx.instructions);
+        let stack_size = passes::replace_pseudos(&mut body);
+
         FnDef {
-            name: tree
-                .tokens
-                .token_text(tree[self.name].token.handle)
-                .to_string(),
-            body: tree[self.body].lower(tree),
+            name: ctx.tree[self.name].name,
+            body: passes::fix_instructions(body, stack_size),
+        }
+    }
+}
+
+impl Lower for ast::Block {
+    type Output = ();
+
+    fn lower(&self, ctx: &mut Context) {
+        for item in &ctx.tree[self.items] {
+            item.lower(ctx);
+        }
+    }
+}
+
+impl Lower for ast::BlockItem {
+    type Output = ();
+
+    fn lower(&self, ctx: &mut Context) {
+        match self {
+            ast::BlockItem::Stmt(stmt) => ctx.tree[stmt].lower(ctx),
+            ast::BlockItem::Decl(decl) => ctx.tree[decl].lower(ctx),
+        }
+    }
+}
+
+impl Lower for ast::Decl {
+    type Output = ();
+
+    fn lower(&self, ctx: &mut Context) {
+        if let Some(init) = self.init {
+            let value = ctx.tree[init].lower(ctx);
+            let var = ctx.tree[self.name].lower(ctx);
+
+            ctx.push(Instruction::Mov {
+                src: value,
+                dst: var,
+            });
         }
     }
 }
 
 impl Lower for ast::Stmt {
-    type Output = Vec<Instruction>;
+    type Output = ();
 
-    fn lower(&self, tree: &Tree) -> Vec<Instruction> {
-        let mut instrs = Vec::new();
+    fn lower(&self, ctx: &mut Context) {
         match self {
             ast::Stmt::Return { expr, .. } => {
-                instrs.push(Instruction::Mov {
-                    src: Operand::Immediate(tree[expr].lower(tree)),
-                    dst: Operand::Register,
+                let value = ctx.tree[expr].lower(ctx);
+
+                ctx.push(Instruction::Mov {
+                    src: value,
+                    dst: Operand::Register(Register::Ax),
                 });
-                instrs.push(Instruction::Ret);
+                ctx.push(Instruction::Ret);
+            }
+            ast::Stmt::Expression { expr } => {
+                ctx.tree[expr].lower(ctx);
             }
-            ast::Stmt::If { .. } => todo!(),
+            ast::Stmt::Compound { block } => ctx.tree[block].lower(ctx),
+            ast::Stmt::Null => (),
+            ast::Stmt::If {
+                cond,
+                cond_true,
+                cond_false,
+            } => {
+                let else_label = ctx.label("if_else");
+                let end = ctx.label("if_end");
+
+                let cond = ctx.tree[cond].lower(ctx);
+                ctx.push(Instruction::Cmp {
+                    src: Operand::Immediate(0),
+                    dst: cond,
+                });
+
+                match cond_false {
+                    Some(cond_false) => {
+                        ctx.push(Instruction::JmpCC {
+                            cond: CondCode::E,
+                            target: else_label,
+                        });
+                        ctx.tree[cond_true].lower(ctx);
+                        ctx.push(Instruction::Jmp(end));
+                        ctx.push(Instruction::Label(else_label));
+                        ctx.tree[cond_false].lower(ctx);
+                    }
+                    None => {
+                        ctx.push(Instruction::JmpCC {
+                            cond: CondCode::E,
+                            target: end,
+                        });
+                        ctx.tree[cond_true].lower(ctx);
+                    }
+                }
+
+                ctx.push(Instruction::Label(end));
+            }
+            ast::Stmt::Error { .. } => unreachable!("Trees with errors are never lowered"),
         };
-        instrs
     }
 }
 
 impl Lower for ast::Expr {
-    type Output = i64;
+    type Output = Operand;
 
-    fn lower(&self, tree: &Tree) -> i64 {
+    /// Lowers the expression, returning where its value ends up
+    fn lower(&self, ctx: &mut Context) -> Operand {
         match self {
-            ast::Expr::Constant { constant } => tree[constant].lower(tree),
+            ast::Expr::Constant { constant } => ctx.tree[constant].lower(ctx),
+            ast::Expr::Var { ident } => ctx.tree[ident].lower(ctx),
+            ast::Expr::Assign { dst, src, .. } => {
+                let value = ctx.tree[src].lower(ctx);
+                let var = ctx.tree[dst].lower(ctx);
+
+                ctx.push(Instruction::Mov {
+                    src: value,
+                    dst: var,
+                });
+
+                var
+            }
+            ast::Expr::CompoundAssign { op, dst, src, .. } => {
+                let value = ctx.tree[src].lower(ctx);
+                // Only variables can be assigned to, so this doesn't evaluate anything
+                let var = ctx.tree[dst].lower(ctx);
+
+                ctx.binary(*op, var, value, var);
+
+                var
+            }
+            ast::Expr::Conditional {
+                cond,
+                cond_true,
+                cond_false,
+            } => {
+                let else_label = ctx.label("cond_else");
+                let end = ctx.label("cond_end");
+                let dst = ctx.temporary();
+
+                let cond = ctx.tree[cond].lower(ctx);
+                ctx.push(Instruction::Cmp {
+                    src: Operand::Immediate(0),
+                    dst: cond,
+                });
+                ctx.push(Instruction::JmpCC {
+                    cond: CondCode::E,
+                    target: else_label,
+                });
+
+                let value = ctx.tree[cond_true].lower(ctx);
+                ctx.push(Instruction::Mov { src: value, dst });
+                ctx.push(Instruction::Jmp(end));
+
+                ctx.push(Instruction::Label(else_label));
+                let value = ctx.tree[cond_false].lower(ctx);
+                ctx.push(Instruction::Mov { src: value, dst });
+
+                ctx.push(Instruction::Label(end));
+
+                dst
+            }
+            ast::Expr::Increment {
+                op,
+                fixity,
+                operand,
+                ..
+            } => {
+                let var = ctx.tree[operand].lower(ctx);
+                let op = match op {
+                    ast::IncrementOp::Increment => BinaryOp::Add,
+                    ast::IncrementOp::Decrement => BinaryOp::Sub,
+                };
+
+                // Postfix operators evaluate to the value from before
+                let result = match fixity {
+                    ast::Fixity::Prefix => var,
+                    ast::Fixity::Postfix => {
+                        let old = ctx.temporary();
+                        ctx.push(Instruction::Mov { src: var, dst: old });
+                        old
+                    }
+                };
+
+                ctx.push(Instruction::Binary {
+                    op,
+                    src: Operand::Immediate(1),
+                    dst: var,
+                });
+
+                result
+            }
+            ast::Expr::Unary { op, operand } => {
+                let src = ctx.tree[operand].lower(ctx);
+                let dst = ctx.temporary();
+
+                match op {
+                    ast::UnaryOp::Negate | ast::UnaryOp::Complement => {
+                        let op = match op {
+                            ast::UnaryOp::Negate => UnaryOp::Neg,
+                            _ => UnaryOp::Not,
+                        };
+
+                        ctx.push(Instruction::Mov { src, dst });
+                        ctx.push(Instruction::Unary { op, operand: dst });
+                    }
+                    // Logical not is a comparison with 0
+                    ast::UnaryOp::Not => {
+                        ctx.push(Instruction::Cmp {
+                            src: Operand::Immediate(0),
+                            dst: src,
+                        });
+                        ctx.push(Instruction::Mov {
+                            src: Operand::Immediate(0),
+                            dst,
+                        });
+                        ctx.push(Instruction::SetCC {
+                            cond: CondCode::E,
+                            dst,
+                        });
+                    }
+                }
+
+                dst
+            }
+            ast::Expr::Binary {
+                op: op @ (ast::BinaryOp::And | ast::BinaryOp::Or),
+                left,
+                right,
+            } => {
+                // The right operand is skipped once the left one decides the result
+                let (short_circuit, name) = match op {
+                    ast::BinaryOp::And => (CondCode::E, "and_false"),
+                    _ => (CondCode::NE, "or_true"),
+                };
+                let short_circuit_label = ctx.label(name);
+                let end = ctx.label("end");
+                let dst = ctx.temporary();
+
+                for operand in [left, right] {
+                    let value = ctx.tree[operand].lower(ctx);
+                    ctx.push(Instruction::Cmp {
+                        src: Operand::Immediate(0),
+                        dst: value,
+                    });
+                    ctx.push(Instruction::JmpCC {
+                        cond: short_circuit,
+                        target: short_circuit_label,
+                    });
+                }
+
+                let (evaluated, short_circuited) = match op {
+                    ast::BinaryOp::And => (1, 0),
+                    _ => (0, 1),
+                };
+
+                ctx.push(Instruction::Mov {
+                    src: Operand::Immediate(evaluated),
+                    dst,
+                });
+                ctx.push(Instruction::Jmp(end));
+                ctx.push(Instruction::Label(short_circuit_label));
+                ctx.push(Instruction::Mov {
+                    src: Operand::Immediate(short_circuited),
+                    dst,
+                });
+                ctx.push(Instruction::Label(end));
+
+                dst
+            }
+            ast::Expr::Binary { op, left, right } => {
+                let left = ctx.tree[left].lower(ctx);
+                let right = ctx.tree[right].lower(ctx);
+                let dst = ctx.temporary();
+
+                ctx.binary(*op, left, right, dst);
+
+                dst
+            }
         }
     }
 }
 
+impl Lower for ast::Ident {
+    type Output = Operand;
+
+    /// Variables are pseudos named after their unique, already renamed symbol
+    fn lower(&self, _ctx: &mut Context) -> Operand {
+        Operand::Pseudo(self.name)
+    }
+}
+
 impl Lower for ast::Constant {
-    type Output = i64;
+    type Output = Operand;
 
-    fn lower(&self, _tree: &Tree) -> i64 {
-        self.value
+    fn lower(&self, _ctx: &mut Context) -> Operand {
+        Operand::Immediate(self.value)
     }
 }
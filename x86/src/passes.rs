@@ -0,0 +1,150 @@
+//! Passes which turn freshly lowered instructions into ones that can actually be emitted
+
+use std::collections::HashMap;
+
+use crate::{BinaryOp, Instruction, Operand, Register};
+
+/// Gives every pseudo its own 4 byte slot in the stack, returns how many bytes they take
+pub(crate) fn replace_pseudos(instructions: &mut [Instruction]) -> i64 {
+    let mut slots = HashMap::new();
+
+    let mut replace = |operand: &mut Operand| {
+        if let Operand::Pseudo(symbol) = *operand {
+            let next = -4 * (slots.len() as i64 + 1);
+            *operand = Operand::Stack(*slots.entry(symbol).or_insert(next));
+        }
+    };
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Mov { src, dst }
+            | Instruction::Binary { src, dst, .. }
+            | Instruction::Cmp { src, dst } => {
+                replace(src);
+                replace(dst);
+            }
+            Instruction::Unary { operand, .. } | Instruction::Idiv(operand) => replace(operand),
+            Instruction::SetCC { dst, .. } => replace(dst),
+            Instruction::Cdq
+            | Instruction::Jmp(_)
+            | Instruction::JmpCC { .. }
+            | Instruction::Label(_)
+            | Instruction::AllocateStack(_)
+            | Instruction::Ret => (),
+        }
+    }
+
+    4 * slots.len() as i64
+}
+
+/// Allocates the stack the function needs and rewrites instructions whose operands x86 doesn't
+/// allow, like moving from memory to memory, going through scratch registers instead
+pub(crate) fn fix_instructions(
+    instructions: Vec<Instruction>,
+    stack_size: i64,
+) -> Vec<Instruction> {
+    let mut fixed = Vec::with_capacity(instructions.len() + 1);
+
+    if stack_size > 0 {
+        fixed.push(Instruction::AllocateStack(stack_size));
+    }
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Mov {
+                src: src @ Operand::Stack(_),
+                dst: dst @ Operand::Stack(_),
+            } => {
+                let scratch = Operand::Register(Register::R10);
+
+                fixed.push(Instruction::Mov { src, dst: scratch });
+                fixed.push(Instruction::Mov { src: scratch, dst });
+            }
+            // The divisor can't be a constant
+            Instruction::Idiv(divisor @ Operand::Immediate(_)) => {
+                let scratch = Operand::Register(Register::R10);
+
+                fixed.push(Instruction::Mov {
+                    src: divisor,
+                    dst: scratch,
+                });
+                fixed.push(Instruction::Idiv(scratch));
+            }
+            // The shift count has to be a constant or %cl
+            Instruction::Binary {
+                op: op @ (BinaryOp::Sal | BinaryOp::Sar | BinaryOp::Shr),
+                src: src @ (Operand::Stack(_) | Operand::Register(_)),
+                dst,
+            } => {
+                let count = Operand::Register(Register::Cx);
+
+                fixed.push(Instruction::Mov { src, dst: count });
+                fixed.push(Instruction::Binary {
+                    op,
+                    src: count,
+                    dst,
+                });
+            }
+            // The product can't be written to memory
+            Instruction::Binary {
+                op: BinaryOp::Mult,
+                src,
+                dst: dst @ Operand::Stack(_),
+            } => {
+                let scratch = Operand::Register(Register::R11);
+
+                fixed.push(Instruction::Mov {
+                    src: dst,
+                    dst: scratch,
+                });
+                fixed.push(Instruction::Binary {
+                    op: BinaryOp::Mult,
+                    src,
+                    dst: scratch,
+                });
+                fixed.push(Instruction::Mov { src: scratch, dst });
+            }
+            Instruction::Binary {
+                op,
+                src: src @ Operand::Stack(_),
+                dst: dst @ Operand::Stack(_),
+            } => {
+                let scratch = Operand::Register(Register::R10);
+
+                fixed.push(Instruction::Mov { src, dst: scratch });
+                fixed.push(Instruction::Binary {
+                    op,
+                    src: scratch,
+                    dst,
+                });
+            }
+            Instruction::Cmp { src, dst } => {
+                let src = match (src, dst) {
+                    (Operand::Stack(_), Operand::Stack(_)) => {
+                        let scratch = Operand::Register(Register::R10);
+                        fixed.push(Instruction::Mov { src, dst: scratch });
+                        scratch
+                    }
+                    _ => src,
+                };
+                // The second operand can't be a constant
+                let dst = match dst {
+                    Operand::Immediate(_) => {
+                        let scratch = Operand::Register(Register::R11);
+                        fixed.push(Instruction::Mov {
+                            src: dst,
+                            dst: scratch,
+                        });
+                        scratch
+                    }
+                    _ => dst,
+                };
+
+                fixed.push(Instruction::Cmp { src, dst });
+            }
+            instruction => fixed.push(instruction),
+        }
+    }
+
+    fixed
+}